@@ -0,0 +1,242 @@
+use crate::client::ResticClient;
+use crate::config::PasswordSource;
+use crate::error::Error;
+use crate::utils::{format_bytes, spawn_restic};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fs::{self, File};
+use std::env;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// zstd 压缩级别 (与 zstd 命令行默认值一致)
+const ZSTD_LEVEL: i32 = 3;
+
+/// 恢复为单个归档文件时支持的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub const ALL: [ArchiveFormat; 4] = [ArchiveFormat::Zip, ArchiveFormat::Tar, ArchiveFormat::TarGz, ArchiveFormat::TarZst];
+
+    /// 解析 `output_format` 字段，例如 "zip"、"tar.gz"、"tgz"、"tar.zst"
+    pub fn parse(value: &str) -> Result<ArchiveFormat, String> {
+        match value.trim().trim_start_matches('.').to_lowercase().as_str() {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            "tar.zst" | "tzst" => Ok(ArchiveFormat::TarZst),
+            "zip" => Ok(ArchiveFormat::Zip),
+            other => Err(format!("不支持的归档格式 '{}' (可选: zip、tar、tar.gz、tar.zst)", other)),
+        }
+    }
+
+    /// 归档文件的扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    /// 根据文件名判断归档格式，不是支持的归档时返回 `None`
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveFormat::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// 传给 `restic dump --archive` 的格式，压缩由本程序在写入时完成
+    fn restic_archive(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            _ => "tar",
+        }
+    }
+}
+
+/// 通过 `restic dump` 将快照中的 `path` (整个快照时为 "/") 写入归档文件 `dest`，返回归档文件大小
+///
+/// restic 只能输出 tar 或 zip，tar.gz / tar.zst 在写入文件时压缩；失败时删除不完整的文件。
+pub fn dump_to_archive(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, snapshot: &str, path: &str, format: ArchiveFormat, dest: &Path) -> Result<u64, Error> {
+    if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录 '{}' 失败: {}", parent.display(), e))?;
+    }
+    let client = ResticClient::new(restic_exe_path, repo_path, password.clone());
+    let result = client.retry_if_locked(|| write_archive(restic_exe_path, repo_path, password, snapshot, path, format, dest));
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+fn write_archive(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, snapshot: &str, path: &str, format: ArchiveFormat, dest: &Path) -> Result<u64, Error> {
+    let args = ["-r", repo_path, "dump", "--archive", format.restic_archive(), snapshot, path];
+    let mut child = spawn_restic(restic_exe_path, &args, password)?;
+
+    // 标准错误单独在线程中读取，避免管道写满导致 restic 阻塞
+    let mut stderr_pipe = child.stderr.take().ok_or("无法读取 restic 标准错误输出")?;
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stderr_pipe.read_to_string(&mut buf);
+        buf
+    });
+
+    let file = BufWriter::new(File::create(dest).map_err(|e| format!("创建归档文件 '{}' 失败: {}", dest.display(), e))?);
+    let mut writer = match format {
+        ArchiveFormat::TarGz => ArchiveWriter::Gz(GzEncoder::new(file, Compression::default())),
+        ArchiveFormat::TarZst => ArchiveWriter::Zst(zstd::Encoder::new(file, ZSTD_LEVEL).map_err(|e| format!("初始化 zstd 压缩失败: {}", e))?),
+        ArchiveFormat::Tar | ArchiveFormat::Zip => ArchiveWriter::Plain(file),
+    };
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(ProgressStyle::default_spinner().template("{spinner:.cyan} [{elapsed_precise}] {msg}").unwrap());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let mut stdout = child.stdout.take().ok_or("无法读取 restic 标准输出")?;
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut total: u64 = 0;
+    let copied = loop {
+        match stdout.read(&mut buf) {
+            Ok(0) => break Ok(()),
+            Ok(n) => {
+                if let Err(e) = writer.write_all(&buf[..n]) {
+                    break Err(e);
+                }
+                total += n as u64;
+                spinner.set_message(format!("正在导出快照数据: {}", format_bytes(total)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => break Err(e),
+        }
+    };
+    // 写入失败时结束 restic，避免其阻塞在已无人读取的管道上
+    if copied.is_err() {
+        let _ = child.kill();
+    }
+    drop(stdout);
+
+    let status = child.wait().map_err(|e| format!("等待 restic 进程失败: {}", e))?;
+    spinner.finish_and_clear();
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(Error::from_restic_output("", &stderr));
+    }
+    copied
+        .and_then(|_| writer.finish())
+        .map_err(|e| format!("写入归档文件 '{}' 失败: {}", dest.display(), e))?;
+    Ok(fs::metadata(dest).map(|m| m.len()).unwrap_or(0))
+}
+
+/// 归档文件写入器，压缩格式需要在结束时显式写出尾部数据
+enum ArchiveWriter {
+    Plain(BufWriter<File>),
+    Gz(GzEncoder<BufWriter<File>>),
+    Zst(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl ArchiveWriter {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            ArchiveWriter::Plain(mut w) => w.flush(),
+            ArchiveWriter::Gz(w) => w.finish()?.flush(),
+            ArchiveWriter::Zst(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ArchiveWriter::Plain(w) => w.write(buf),
+            ArchiveWriter::Gz(w) => w.write(buf),
+            ArchiveWriter::Zst(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ArchiveWriter::Plain(w) => w.flush(),
+            ArchiveWriter::Gz(w) => w.flush(),
+            ArchiveWriter::Zst(w) => w.flush(),
+        }
+    }
+}
+
+/// 展开 `archive` 配置中的路径: 归档文件本身，或目录中的全部归档文件 (按文件名排序，不递归)
+pub fn collect_archives(paths: &[String]) -> Result<Vec<(PathBuf, ArchiveFormat)>, Error> {
+    let mut archives = Vec::new();
+    for path_str in paths {
+        let path = Path::new(path_str);
+        if path.is_dir() {
+            let mut found: Vec<(PathBuf, ArchiveFormat)> = fs::read_dir(path)
+                .map_err(|e| format!("读取目录 '{}' 失败: {}", path.display(), e))?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|p| p.is_file())
+                .filter_map(|p| ArchiveFormat::from_path(&p).map(|format| (p, format)))
+                .collect();
+            if found.is_empty() {
+                return Err(format!("目录 '{}' 中没有可导入的归档文件 (.zip、.tar、.tar.gz、.tar.zst)。", path.display()).into());
+            }
+            found.sort_by(|a, b| a.0.cmp(&b.0));
+            archives.extend(found);
+        } else if !path.exists() {
+            return Err(format!("归档路径 '{}' 不存在。", path.display()).into());
+        } else {
+            let format = ArchiveFormat::from_path(path)
+                .ok_or_else(|| format!("'{}' 不是支持的归档文件 (.zip、.tar、.tar.gz、.tar.zst)。", path.display()))?;
+            archives.push((path.to_path_buf(), format));
+        }
+    }
+    Ok(archives)
+}
+
+/// 导入归档时的暂存目录: 系统临时目录下按任务与归档文件名固定的位置
+///
+/// 同一归档每次都解压到相同路径，restic 才能找到父快照并正确去重。
+pub fn staging_dir(job_name: &str, archive: &Path) -> PathBuf {
+    let archive_name = archive.file_name().unwrap_or_default().to_string_lossy();
+    env::temp_dir().join("xcompress_import").join(job_name).join(archive_name.as_ref())
+}
+
+/// 将归档解压到 `dest` (会先清空该目录)，条目中指向 `dest` 之外的路径会被拒绝或忽略
+pub fn extract_archive(archive: &Path, format: ArchiveFormat, dest: &Path) -> Result<(), Error> {
+    if dest.exists() {
+        fs::remove_dir_all(dest).map_err(|e| format!("清理暂存目录 '{}' 失败: {}", dest.display(), e))?;
+    }
+    fs::create_dir_all(dest).map_err(|e| format!("创建暂存目录 '{}' 失败: {}", dest.display(), e))?;
+
+    let file = BufReader::new(File::open(archive).map_err(|e| format!("打开归档 '{}' 失败: {}", archive.display(), e))?);
+    let extracted = match format {
+        ArchiveFormat::Zip => zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dest))
+            .map_err(|e| e.to_string()),
+        ArchiveFormat::Tar => tar::Archive::new(file).unpack(dest).map_err(|e| e.to_string()),
+        ArchiveFormat::TarGz => tar::Archive::new(GzDecoder::new(file)).unpack(dest).map_err(|e| e.to_string()),
+        ArchiveFormat::TarZst => zstd::Decoder::with_buffer(file)
+            .and_then(|decoder| tar::Archive::new(decoder).unpack(dest))
+            .map_err(|e| e.to_string()),
+    };
+    extracted.map_err(|e| format!("解压归档 '{}' 失败: {}", archive.display(), e).into())
+}
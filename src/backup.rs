@@ -1,373 +1,471 @@
-use crate::config::{self, FinalConfig};
-use crate::utils::{is_restic_repo, run_restic_command};
+use crate::archive;
+use crate::config::{BackupSource, ExcludeOptions, FinalConfig, PasswordSource};
+use crate::error::Error;
+use crate::check;
+use crate::client::{BackupRequest, Compression, InitOptions, ResticClient};
+use crate::diff;
+use crate::forget;
+use crate::history;
+use crate::hooks;
+use crate::lock::UnlockPolicy;
+use crate::progress::{with_spinner, BackupProgress, BackupSummary, JobOutput};
+use crate::report::{JobReport, JobResult};
+use crate::utils;
 use console::style;
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
-use std::env;
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
-
-pub fn handle_backup(restic_exe_path: &str, config_path: Option<String>, target_path: Option<String>) {
-    println!("\n{}\n", style("--- 开始备份流程 ---").bold().yellow());
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+/// 不来自配置文件的一次性备份 (命令行 `backup` 子命令与交互式备份)
+#[derive(Debug, Clone)]
+pub struct DirectBackup {
+    /// 运行历史中的任务名，例如 "cli"、"interactive"
+    pub job_name: String,
+    pub paths: Vec<String>,
+    pub repo: String,
+    pub passwd: PasswordSource,
+    pub tag: String,
+    pub pack_size: u64,
+    pub compression: Option<Compression>,
+    pub exclude: ExcludeOptions,
+    /// 仓库不存在、需要新建时的选项
+    pub init: InitOptions,
+    pub unlock: UnlockPolicy,
+}
 
-    if let Some(path) = config_path {
-        // 模式一：使用指定的 toml 配置文件
-        match config::parse_toml(&path) {
-            Ok(configs) => run_toml_backup(restic_exe_path, configs),
-            Err(e) => eprintln!("{} {}", style("✖").red(), style(e).red().bold()),
-        }
-    } else if let Some(path) = target_path {
-        // 模式二：直接备份指定的路径 (交互式)
-        if !Path::new(&path).exists() {
-            eprintln!("{} {}", style("✖").red(), style(format!("错误: 提供的路径 '{}' 不存在。", path)).red().bold());
-        } else {
-            run_interactive_backup(restic_exe_path, Some(path)).unwrap_or_else(|e| {
-                eprintln!("{} {}", style("✖").red(), style(format!("交互式备份失败: {}", e)).red().bold());
-            });
+/// 将每个路径单独备份到指定的仓库并记录运行历史，不弹出任何提示
+pub fn run_direct_backup(restic_exe_path: &str, backup: &DirectBackup, output: &JobOutput) -> JobReport {
+    let started = Instant::now();
+    let bytes_added = Cell::new(None);
+    let result = match backup.paths.iter().find(|p| !Path::new(p).exists()) {
+        Some(missing) => Err(format!("提供的路径 '{}' 不存在。", missing).into()),
+        None => {
+            let repo_path = PathBuf::from(&backup.repo);
+            output.println(format!("{} 仓库路径: {}", style("→").dim(), repo_path.display()));
+            let repo_version = Cell::new(None);
+            let options = BackupOptions {
+                tag: &backup.tag,
+                pack_size: backup.pack_size,
+                compression: backup.compression,
+                exclude: &backup.exclude,
+                init: &backup.init,
+                unlock: backup.unlock.clone().suspending(output),
+                output,
+                bytes_added: &bytes_added,
+                repo_version: &repo_version,
+            };
+            backup_individual(restic_exe_path, &backup.paths, &repo_path, &backup.passwd, &options)
         }
+    };
+
+    let report = JobReport::new(&backup.job_name, &backup.repo, started, result)
+        .with_sources(&backup.paths)
+        .with_bytes_added(bytes_added.get());
+    history::record("backup", std::slice::from_ref(&report), output);
+    report
+}
+
+/// 按顺序 (或并行) 执行配置文件中的备份任务
+///
+/// `max_parallel` 大于 1 时，依赖已完成且仓库不同的任务会同时运行；同一仓库的任务始终依次执行，
+/// 避免 restic 仓库锁冲突。任务报告与汇总始终按配置中的执行顺序排列。
+pub fn run_toml_backup(restic_exe_path: &str, configs: Vec<FinalConfig>, max_parallel: usize, output: &JobOutput) -> Vec<JobReport> {
+    let max_parallel = max_parallel.clamp(1, configs.len().max(1));
+    let output = if max_parallel > 1 {
+        output.println(format!("{} 并行执行，最多同时运行 {} 个任务 (同一仓库的任务依次执行)。", style("i").blue(), max_parallel));
+        output.parallel()
     } else {
-        // 模式三：无参数，检查默认 toml 或进入交互式
-        // 优先检查 backup_config.toml，其次检查 backup.toml
-        let default_tomls = vec!["backup_config.toml", "backup.toml"];
-        let mut found_toml = None;
-        for t in default_tomls {
-            if Path::new(t).exists() {
-                found_toml = Some(t);
+        output.clone()
+    };
+
+    let repo_paths: Vec<PathBuf> = configs.iter().map(FinalConfig::repo_path).collect();
+    let mut outcomes: Vec<Option<(String, JobReport)>> = configs.iter().map(|_| None).collect();
+    let mut launched = vec![false; configs.len()];
+    let mut busy_repos: HashSet<&Path> = HashSet::new();
+    // 已结束的任务，以及其中失败或被跳过的任务 (依赖它们的任务也会被跳过)
+    let mut finished: HashSet<&str> = HashSet::new();
+    let mut unfinished: HashSet<&str> = HashSet::new();
+    let mut running = 0;
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        loop {
+            // 配置已按依赖关系排好序，按顺序扫描一遍即可启动所有就绪的任务
+            for (i, config) in configs.iter().enumerate() {
+                if launched[i] || running >= max_parallel {
+                    continue;
+                }
+                if !config.depends_on.iter().all(|dep| finished.contains(dep.as_str())) {
+                    continue;
+                }
+
+                let failed_deps: Vec<&str> = config.depends_on.iter().map(String::as_str).filter(|dep| unfinished.contains(dep)).collect();
+                if !failed_deps.is_empty() {
+                    launched[i] = true;
+                    let e = format!("跳过: 依赖任务 {} 未成功完成。", failed_deps.join(", "));
+                    let job_output = output.for_job(&config.key_name);
+                    job_output.println(format!("\n{}", style(format!("--- 处理任务: {} ({}) ---", config.key_name, config.name)).cyan().bold()));
+                    job_output.println(format!("{} {}", style("!").yellow(), e));
+                    let line = format!("{} {}: {}", style("-").yellow(), config.key_name, e);
+                    let report = JobReport::new(&config.key_name, &repo_paths[i].to_string_lossy(), Instant::now(), Err(e.into())).with_sources(&config.path);
+                    outcomes[i] = Some((line, report));
+                    finished.insert(&config.key_name);
+                    unfinished.insert(&config.key_name);
+                    continue;
+                }
+
+                if !busy_repos.insert(&repo_paths[i]) {
+                    continue;
+                }
+                launched[i] = true;
+                running += 1;
+                let tx = tx.clone();
+                let job_output = output.for_job(&config.key_name);
+                let repo_path = &repo_paths[i];
+                scope.spawn(move || {
+                    let started = Instant::now();
+                    let bytes_added = Cell::new(None);
+                    let repo_version = Cell::new(None);
+                    let options = backup_options(config, &job_output, &bytes_added, &repo_version);
+                    let result = utils::with_restic_env(&config.env, || {
+                        config.restic_exe_path(restic_exe_path).and_then(|exe| run_backup_job(exe, config, repo_path, &options))
+                    });
+                    let _ = tx.send((i, started, result, bytes_added.get()));
+                });
+            }
+
+            if running == 0 {
                 break;
             }
-        }
-        
-        if let Some(toml_file) = found_toml {
-            println!("{} 检测到默认配置文件 '{}'，将使用该文件进行备份。", style("i").blue(), toml_file);
-            match config::parse_toml(toml_file) {
-                Ok(configs) => run_toml_backup(restic_exe_path, configs),
-                Err(e) => eprintln!("{} {}", style("✖").red(), style(e).red().bold()),
+            let Ok((i, started, result, bytes_added)) = rx.recv() else { break };
+            running -= 1;
+            let config = &configs[i];
+            busy_repos.remove(repo_paths[i].as_path());
+            finished.insert(&config.key_name);
+
+            let line = match &result {
+                Ok((msg, _)) => format!("{} {}: {}", style("✔").green(), config.key_name, msg),
+                Err(e) => {
+                    unfinished.insert(&config.key_name);
+                    format!("{} {}: {}", style("✖").red(), config.key_name, e)
+                }
+            };
+            if max_parallel > 1 {
+                output.println(&line);
             }
-        } else {
-            println!("{} 未提供参数且未找到默认配置文件，进入交互式备份模式。", style("i").blue());
-            run_interactive_backup(restic_exe_path, None).unwrap_or_else(|e| {
-                eprintln!("{} {}", style("✖").red(), style(format!("交互式备份失败: {}", e)).red().bold());
-            });
+            let report = JobReport::new(&config.key_name, &repo_paths[i].to_string_lossy(), started, result)
+                .with_sources(&config.path)
+                .with_bytes_added(bytes_added);
+            outcomes[i] = Some((line, report));
         }
-    }
+    });
+
+    let (summary, reports): (Vec<String>, Vec<JobReport>) = outcomes.into_iter().flatten().unzip();
+    output.println(format!("\n\n{}\n{}", style("===== 备份汇总 =====").yellow().bold(), summary.join("\n")));
+    history::record("backup", &reports, &output);
+    reports
 }
 
-pub fn handle_batch_backup(restic_exe_path: &str) -> Result<(), String> {
-    println!("\n{}\n", style("--- 开始批量备份流程 ---").bold().yellow());
-    let theme = ColorfulTheme::default();
+/// 执行单个备份任务，并在前后运行配置的钩子命令
+fn run_backup_job(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path, options: &BackupOptions) -> JobResult {
+    let output = options.output;
+    output.println(format!("\n{}", style(format!("--- 处理任务: {} ({}) ---", config.key_name, config.name)).cyan().bold()));
+    output.println(format!("{} 仓库路径: {}", style("→").dim(), repo_path.display()));
+    if config.restic_binary.is_some() {
+        output.println(format!("{} restic: {}", style("→").dim(), restic_exe_path));
+    }
 
-    // 智能设置默认值
-    let default_val = if Path::new("backup_config.toml").exists() {
-        "backup_config.toml"
-    } else if Path::new("backup.toml").exists() {
-        "backup.toml"
-    } else {
-        "backup_config.toml"
+    let job_hooks = &config.hooks;
+    let timeout = hooks::timeout_from_secs(job_hooks.hook_timeout);
+    let run_hook = |name: &str, command: &str, result: Option<&JobResult>| {
+        hooks::run_hook(name, command, &hooks::job_env(config, repo_path, name, result), timeout, output)
     };
-
-    // 1. Get TOML config path
-    let config_path: String = Input::with_theme(&theme)
-        .with_prompt("请输入或拖入 backup_config.toml 文件路径")
-        .default(default_val.into())
-        .validate_with(|input: &String| -> Result<(), &str> {
-            if Path::new(input).exists() { Ok(()) } else { Err("文件不存在，请重新输入。") }
-        })
-        .interact_text()
-        .map_err(|e| e.to_string())?;
-    
-    // 2. Parse TOML and run backups
-    match config::parse_toml(&config_path) {
-        Ok(configs) => {
-            run_toml_backup(restic_exe_path, configs);
-            Ok(())
+    let mut notes = Vec::new();
+
+    // 前置命令失败时默认放弃本次备份，但之后仍会执行 post_command 以便恢复现场 (例如重新启动服务)
+    let pre_error = match &job_hooks.pre_command {
+        Some(command) => match run_hook("pre_command", command, None) {
+            Ok(note) => {
+                notes.push(note);
+                None
+            }
+            Err(e) if job_hooks.abort_on_pre_failure.unwrap_or(true) => Some(e),
+            Err(e) => {
+                output.println(format!("{} {}，继续备份。", style("!").yellow(), e));
+                notes.push(e);
+                None
+            }
         },
-        Err(e) => Err(e),
-    }
-}
-
-
-fn run_toml_backup(restic_exe_path: &str, configs: Vec<FinalConfig>) {
-    println!("{} 成功解析配置文件，共找到 {} 个备份任务。", style("✔").green(), configs.len());
-    let mut summary = Vec::new();
+        None => None,
+    };
 
-    for config in configs {
-        println!("\n{}", style(format!("--- 处理任务: {} ({}) ---", config.key_name, config.name)).cyan().bold());
-        let final_repo_path = PathBuf::from(&config.restic_home_path).join(&config.name);
-        println!("{} 仓库路径: {}", style("→").dim(), final_repo_path.display());
-        
-        let result = if config.merge == 1 {
-            backup_merged(restic_exe_path, &config, &final_repo_path)
-        } else {
-            backup_individual(restic_exe_path, &config, &final_repo_path)
-        };
+    let mut result = match pre_error {
+        Some(e) => Err(format!("{}，已放弃本次备份。", e).into()),
+        None => run_backup_steps(restic_exe_path, config, repo_path, options),
+    };
 
-        match result {
-            Ok(msg) => summary.push(format!("{} {}: {}", style("✔").green(), config.key_name, msg)),
-            Err(e) => summary.push(format!("{} {}: {}", style("✖").red(), config.key_name, e)),
+    if let Some(command) = &job_hooks.post_command {
+        match run_hook("post_command", command, Some(&result)) {
+            Ok(note) => notes.push(note),
+            Err(e) => {
+                result = match result {
+                    Ok((msg, _)) => Err(format!("{} 但 {}", msg, e).into()),
+                    Err(err) => Err(err.with_message(|err| format!("{}\n{}", err, e))),
+                }
+            }
         }
     }
 
-    println!("\n\n{}\n{}", style("===== 备份汇总 =====").yellow().bold(), summary.join("\n"));
+    // on_success / on_failure 的结果只记录在摘要中，不改变任务本身的成败
+    let (name, command) = match &result {
+        Ok(_) => ("on_success", &job_hooks.on_success),
+        Err(_) => ("on_failure", &job_hooks.on_failure),
+    };
+    if let Some(command) = command {
+        notes.push(run_hook(name, command, Some(&result)).unwrap_or_else(|e| e));
+    }
+
+    if notes.is_empty() {
+        return result;
+    }
+    let notes = format!(" 钩子: {}。", notes.join("; "));
+    match result {
+        Ok((msg, snapshot_ids)) => Ok((msg + &notes, snapshot_ids)),
+        Err(e) => Err(e.with_message(|e| format!("{}{}", e, notes))),
+    }
 }
 
-fn backup_merged(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path) -> Result<String, String> {
-    println!("{} 模式: 合并备份 (共 {} 个路径)", style("→").dim(), config.path.len());
-    
-    // 创建临时合并目录
-    let temp_dir_name = format!("{}_{}", config.merge_name, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs());
-    let merge_path = env::temp_dir().join(temp_dir_name);
-    fs::create_dir_all(&merge_path).map_err(|e| format!("创建合并目录失败: {}", e))?;
-
-    // 复制文件/目录到合并目录
-    let mut copy_errors = Vec::new();
-    for src_path_str in &config.path {
-        let src_path = Path::new(src_path_str);
-        let dest_path = merge_path.join(src_path.file_name().unwrap_or_default());
-        print!("  - 正在复制 {} 到 {} ... ", style(src_path.display()).dim(), style(dest_path.display()).dim());
-        
-        let result = if src_path.is_dir() {
-            fs_extra::dir::copy(src_path, &merge_path, &fs_extra::dir::CopyOptions::new())
-        } else {
-            fs_extra::file::copy(src_path, &dest_path, &fs_extra::file::CopyOptions::new())
-        };
+/// 任务本身的步骤: 备份、按保留策略清理旧快照、按需校验仓库
+fn run_backup_steps(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path, options: &BackupOptions) -> JobResult {
+    let output = options.output;
 
-        match result {
-            Ok(_) => println!("{}", style("完成").green()),
-            Err(e) => {
-                let err_msg = format!("复制 {} 失败: {}", src_path_str, e);
-                println!("{} {}", style("失败").red(), style(&err_msg).red());
-                copy_errors.push(err_msg);
+    let result = if config.source == BackupSource::Archives {
+        import_archives(restic_exe_path, config, repo_path, options)
+    } else if config.merge == 1 {
+        backup_merged(restic_exe_path, config, repo_path, options)
+    } else {
+        backup_individual(restic_exe_path, &config.path, repo_path, &config.passwd, options)
+    };
+
+    // 按需打印与父快照之间的差异 (在清理旧快照之前，父快照此时一定还在)，失败时只提示
+    if let (true, Ok((_, snapshot_ids))) = (config.diff_after_backup, &result) {
+        for snapshot_id in snapshot_ids {
+            if let Err(e) = diff::print_parent_diff(restic_exe_path, &repo_path.to_string_lossy(), &config.passwd, snapshot_id, output) {
+                output.println(format!("{} 比较快照 {} 与父快照失败: {}", style("!").yellow(), snapshot_id, e));
             }
         }
     }
-    
-    if !copy_errors.is_empty() {
-        // 清理临时目录
-        let _ = fs::remove_dir_all(&merge_path);
-        return Err(format!("文件复制阶段出现错误:\n{}", copy_errors.join("\n")));
-    }
-    
-    // 执行备份
-    println!("{} 开始备份合并目录 {} ...", style("→").dim(), merge_path.display());
-    let backup_result = execute_backup(restic_exe_path, repo_path, &merge_path, &config.passwd, &config.tag, config.pack_size);
 
-    // 清理临时目录
-    let _ = fs::remove_dir_all(&merge_path);
+    // 备份成功后按保留策略清理旧快照
+    let result = result.and_then(|(msg, snapshot_ids)| {
+        if !config.retention.has_rules() {
+            return Ok((msg, snapshot_ids));
+        }
+        match forget::run_forget(restic_exe_path, &repo_path.to_string_lossy(), &config.passwd, &config.retention, options.unlock.clone(), output) {
+            Ok(removed) if removed.is_empty() => Ok((format!("{} 保留策略: 无需移除快照。", msg), snapshot_ids)),
+            Ok(removed) => Ok((format!("{} 保留策略: 移除了 {} 个快照 ({})。", msg, removed.len(), removed.join(", ")), snapshot_ids)),
+            Err(e) => Err(e.with_message(|e| format!("{} 但按保留策略清理旧快照失败: {}", msg, e))),
+        }
+    });
 
-    backup_result
+    // 按需校验仓库完整性
+    result.and_then(|(msg, snapshot_ids)| {
+        if !config.check_after_backup {
+            return Ok((msg, snapshot_ids));
+        }
+        match check::run_check(restic_exe_path, &repo_path.to_string_lossy(), &config.passwd, config.read_data_subset.as_deref(), options.unlock.clone(), output) {
+            Ok(_) => Ok((format!("{} 仓库校验通过。", msg), snapshot_ids)),
+            Err(e) => Err(e.with_message(|e| format!("{} 但仓库校验失败: {}", msg, e))),
+        }
+    })
 }
 
-fn backup_individual(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path) -> Result<String, String> {
-    println!("{} 模式: 单独备份 (共 {} 个路径)", style("→").dim(), config.path.len());
+fn backup_merged(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path, options: &BackupOptions) -> Result<(String, Vec<String>), Error> {
+    options.output.println(format!("{} 模式: 合并备份 (共 {} 个路径，生成单个快照)", style("→").dim(), config.path.len()));
+
+    // 所有路径一次性交给 restic，快照中保留各自的原始路径，
+    // 因此每次备份的路径集合稳定，restic 能找到父快照，也无需复制任何文件
+    let paths: Vec<&Path> = config.path.iter().map(Path::new).collect();
+    for p in &paths {
+        options.output.println(format!("  - {}", style(p.display()).dim()));
+    }
+
+    let summary = execute_backup(restic_exe_path, repo_path, &paths, &config.passwd, options)?;
+    Ok((format!("合并备份成功，{}", summary.describe()), summary.short_id().into_iter().collect()))
+}
+
+fn backup_individual(restic_exe_path: &str, paths: &[String], repo_path: &Path, passwd: &PasswordSource, options: &BackupOptions) -> Result<(String, Vec<String>), Error> {
+    options.output.println(format!("{} 模式: 单独备份 (共 {} 个路径)", style("→").dim(), paths.len()));
     let mut success_count = 0;
+    let mut snapshot_ids = Vec::new();
     let mut path_errors = Vec::new();
+    let mut first_error = None;
 
-    for path_str in &config.path {
+    for path_str in paths {
         let backup_path = Path::new(path_str);
-        println!("  - 正在备份 {} ...", style(backup_path.display()).dim());
-        match execute_backup(restic_exe_path, repo_path, backup_path, &config.passwd, &config.tag, config.pack_size) {
-            Ok(_) => success_count += 1,
-            Err(e) => path_errors.push(format!("路径 {} 备份失败: {}", path_str, e)),
+        options.output.println(format!("  - 正在备份 {} ...", style(backup_path.display()).dim()));
+        match execute_backup(restic_exe_path, repo_path, &[backup_path], passwd, options) {
+            Ok(summary) => {
+                options.output.println(format!("    {} {}", style("✔").green(), summary.describe()));
+                success_count += 1;
+                snapshot_ids.extend(summary.short_id());
+            }
+            Err(e) => {
+                path_errors.push(format!("路径 {} 备份失败: {}", path_str, e));
+                first_error.get_or_insert(e);
+            }
         }
     }
     
-    if path_errors.is_empty() {
-        Ok(format!("所有 {} 个路径单独备份成功。", success_count))
-    } else {
-        Err(format!("{}/{} 个路径备份成功，错误详情:\n{}", success_count, config.path.len(), path_errors.join("\n")))
+    // 错误类别取自第一个失败的路径，以便按退出码区分密码错误、仓库被锁定等情况
+    match first_error {
+        None => Ok((format!("所有 {} 个路径单独备份成功。", success_count), snapshot_ids)),
+        Some(e) => Err(e.with_message(|_| format!("{}/{} 个路径备份成功，错误详情:\n{}", success_count, paths.len(), path_errors.join("\n")))),
     }
 }
 
-fn run_interactive_backup(restic_exe_path: &str, target_path: Option<String>) -> Result<(), String> {
-    let theme = ColorfulTheme::default();
-
-    // 1. Get path to back up
-    let backup_path_str = match &target_path {
-        Some(path) => path.clone(),
-        None => Input::with_theme(&theme)
-            .with_prompt("请输入或拖入要备份的文件/目录路径")
-            .validate_with(|input: &String| -> Result<(), &str> {
-                if Path::new(input).exists() { Ok(()) } else { Err("路径不存在，请重新输入。") }
-            })
-            .interact_text().map_err(|e| e.to_string())?,
-    };
-    let backup_path = Path::new(&backup_path_str);
-
-    // --- 智能仓库路径选择 logic ---
-    let mut repo_path_option: Option<PathBuf> = None;
-
-    // 如果是通过拖拽/命令行直接传入路径，进入“快速模式”
-    if target_path.is_some() {
-        let parent_dir = backup_path.parent().unwrap_or(Path::new("."));
-        let dir_name = backup_path.file_name().unwrap_or_default().to_string_lossy();
-        // 默认推荐: 在源目录旁边创建一个 xxx_repo 的目录
-        let suggested_repo_name = format!("{}_repo", dir_name);
-        let suggested_repo_path = parent_dir.join(&suggested_repo_name);
-
-        println!("\n{}", style("--- 快速备份模式 ---").cyan().bold());
-        
-        let opts = vec![
-            format!("使用默认仓库位置: {} (推荐)", style(suggested_repo_path.display()).green()),
-            "自定义位置 / 选择现有仓库".to_string()
-        ];
-
-        let sel = Select::with_theme(&theme)
-            .with_prompt(format!("检测到源目录 '{}'，请选择备份方案", dir_name))
-            .items(&opts)
-            .default(0)
-            .interact()
-            .unwrap_or(0);
-
-        if sel == 0 {
-            repo_path_option = Some(suggested_repo_path);
-        }
-    }
-
-    // 确定最终的 repo_path (解决编译器重复赋值/未初始化问题)
-    let repo_path = if let Some(path) = repo_path_option {
-        path
-    } else {
-        // --- 传统手动模式：选择 Base Dir -> 扫描/新建 ---
-        let exe_dir = env::current_dir().map_err(|e| format!("获取当前目录失败: {}", e))?;
-        let default_repo_base = exe_dir.to_string_lossy().to_string();
-        let repo_base_str: String = Input::with_theme(&theme)
-            .with_prompt("请输入 restic 仓库的存放目录 (留空则使用当前程序目录)")
-            .default(default_repo_base)
-            .interact_text()
-            .map_err(|e| e.to_string())?;
-        let repo_base_path = Path::new(&repo_base_str);
-
-        if !repo_base_path.exists() || !repo_base_path.is_dir() {
-            return Err(format!("仓库存放目录 '{}' 不是一个有效的目录。", repo_base_str));
-        }
+/// 逐个解压归档到固定的暂存目录并备份，每个归档生成一个带有归档文件名标签的快照
+fn import_archives(restic_exe_path: &str, config: &FinalConfig, repo_path: &Path, options: &BackupOptions) -> Result<(String, Vec<String>), Error> {
+    let archives = archive::collect_archives(&config.path)?;
+    options.output.println(format!("{} 模式: 导入归档 (共 {} 个归档，每个生成一个快照)", style("→").dim(), archives.len()));
+    let mut success_count = 0;
+    let mut snapshot_ids = Vec::new();
+    let mut archive_errors = Vec::new();
+    let mut first_error = None;
+
+    for (archive_path, format) in &archives {
+        options.output.println(format!("  - 正在导入 {} ...", style(archive_path.display()).dim()));
+        let staging = archive::staging_dir(&config.key_name, archive_path);
+        // restic 的 --tag 以逗号分隔多个标签，归档名中的逗号需要替换
+        let archive_tag = archive_path.file_name().unwrap_or_default().to_string_lossy().replace(',', "_");
+        let tag = if options.tag.is_empty() { archive_tag } else { format!("{},{}", options.tag, archive_tag) };
+        let result = archive::extract_archive(archive_path, *format, &staging)
+            .and_then(|_| execute_backup(restic_exe_path, repo_path, &[staging.as_path()], &config.passwd, &BackupOptions { tag: &tag, ..options.clone() }));
+        let _ = fs::remove_dir_all(&staging);
 
-        println!("\n{} 正在扫描 '{}' 下的 restic 仓库...", style("i").blue(), repo_base_path.display());
-        let mut existing_repos = Vec::new();
-        if let Ok(entries) = fs::read_dir(repo_base_path) {
-            for entry in entries.filter_map(Result::ok) {
-                let path = entry.path();
-                if path.is_dir() && is_restic_repo(&path) {
-                    existing_repos.push(path);
-                }
+        match result {
+            Ok(summary) => {
+                options.output.println(format!("    {} {}", style("✔").green(), summary.describe()));
+                success_count += 1;
+                snapshot_ids.extend(summary.short_id());
             }
-        }
-
-        let mut selection_items: Vec<String> = existing_repos
-            .iter()
-            .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
-            .collect();
-
-        selection_items.sort();
-        let create_new_option = "[ 创建一个新的备份仓库 ]".to_string();
-        selection_items.push(create_new_option.clone());
-
-        let selection = Select::with_theme(&theme)
-            .with_prompt("请选择要使用的备份仓库")
-            .items(&selection_items)
-            .default(0)
-            .interact()
-            .map_err(|e| e.to_string())?;
-
-        if selection_items[selection] == create_new_option {
-            loop {
-                let backup_name: String = Input::with_theme(&theme)
-                    .with_prompt("请输入新备份仓库的名称")
-                    .interact_text().map_err(|e| e.to_string())?;
-                let potential_path = repo_base_path.join(&backup_name);
-                if potential_path.exists() {
-                    eprintln!("{}", style(format!("错误: 目录 '{}' 已存在。", potential_path.display())).red());
-                } else {
-                    break potential_path;
-                }
+            Err(e) => {
+                archive_errors.push(format!("归档 {} 导入失败: {}", archive_path.display(), e));
+                first_error.get_or_insert(e);
             }
-        } else {
-            repo_base_path.join(&selection_items[selection])
         }
-    };
+    }
 
-    // 6. Get password and confirm
-    let password = Password::with_theme(&theme)
-        .with_prompt(format!("请输入仓库 '{}' 的密码", repo_path.file_name().unwrap_or_default().to_string_lossy()))
-        .with_confirmation("请再次输入密码确认", "两次输入的密码不匹配。")
-        .interact().map_err(|e| e.to_string())?;
-
-    // 7. 每次备份前询问 Pack Size (默认值修改为 128)
-    let size_opts = vec![
-        "最大 (128 MiB) - 文件少易上传网盘 / 压缩率最高 / 推荐 TB 级数据",
-        "默认 (16 MiB) - 文件碎片较多 / 本地性能最高 / 压缩率中等",
-        "自定义大小 (16-128 MiB)"
-    ];
-    let size_selection = Select::with_theme(&theme)
-        .with_prompt("请选择本次备份的存储单元大小 (Pack Size)")
-        .items(&size_opts)
-        .default(0)
-        .interact()
-        .map_err(|e| e.to_string())?;
-
-    let pack_size = match size_selection {
-        0 => 128,
-        1 => 16,
-        2 => Input::with_theme(&theme)
-            .with_prompt("请输入 Pack Size (MiB)")
-            .default(128)
-            .validate_with(|input: &u64| -> Result<(), &str> {
-                if *input >= 16 && *input <= 128 { Ok(()) } else { Err("范围必须在 16 到 128 之间") }
-            })
-            .interact_text()
-            .map_err(|e| e.to_string())?,
-        _ => 128,
-    };
+    match first_error {
+        None => Ok((format!("所有 {} 个归档导入成功。", success_count), snapshot_ids)),
+        Some(e) => Err(e.with_message(|_| format!("{}/{} 个归档导入成功，错误详情:\n{}", success_count, archives.len(), archive_errors.join("\n")))),
+    }
+}
 
-    println!("\n{} 准备将 '{}' 备份到 '{}'...", style("i").blue(), backup_path.display(), repo_path.display());
+/// 传递给 `restic backup` 的可选参数
+#[derive(Clone)]
+struct BackupOptions<'a> {
+    tag: &'a str,
+    pack_size: u64,
+    compression: Option<Compression>,
+    exclude: &'a ExcludeOptions,
+    /// 仓库不存在、需要新建时的选项
+    init: &'a InitOptions,
+    /// 仓库被锁定时的处理方式 (同时用于清理旧快照与校验)
+    unlock: UnlockPolicy,
+    output: &'a JobOutput,
+    /// 累计本任务新增到仓库的数据量，用于运行历史
+    bytes_added: &'a Cell<Option<u64>>,
+    /// 仓库格式版本，设置了压缩模式时每个任务只查询一次
+    repo_version: &'a Cell<Option<u32>>,
+}
 
-    if !Confirm::with_theme(&theme).with_prompt("确认开始备份吗?").interact().unwrap_or(false) {
-        println!("{}", style("操作已取消。").yellow());
-        return Ok(());
+fn backup_options<'a>(config: &'a FinalConfig, output: &'a JobOutput, bytes_added: &'a Cell<Option<u64>>, repo_version: &'a Cell<Option<u32>>) -> BackupOptions<'a> {
+    BackupOptions {
+        tag: &config.tag,
+        pack_size: config.pack_size,
+        compression: config.compression,
+        exclude: &config.exclude,
+        init: &config.init,
+        unlock: config.unlock_policy().reported(output),
+        output,
+        bytes_added,
+        repo_version,
     }
+}
 
-    // 8. Execute backup
-    match execute_backup(restic_exe_path, &repo_path, backup_path, &password, "", pack_size) {
-        Ok(msg) => {
-            println!("{}\n{}", style("✔ 交互式备份成功!").green().bold(), msg);
-            Ok(())
-        },
-        Err(e) => Err(e),
+/// 仓库格式版本，同一任务内只查询一次
+fn cached_repo_version(client: &ResticClient, options: &BackupOptions) -> Result<u32, Error> {
+    if let Some(version) = options.repo_version.get() {
+        return Ok(version);
     }
+    let version = with_spinner(options.output, || client.repo_version())?;
+    options.repo_version.set(Some(version));
+    Ok(version)
 }
 
-/// 核心备份执行函数
-fn execute_backup(restic_exe_path: &str, repo_path: &Path, backup_path: &Path, passwd: &str, tag: &str, pack_size: u64) -> Result<String, String> {
-    // 1. 如果仓库不存在，则自动初始化
-    if !is_restic_repo(repo_path) {
-        if repo_path.exists() && repo_path.read_dir().unwrap().next().is_some() {
-            return Err(format!("目录 {} 已存在但不是有效的 restic 仓库。", repo_path.display()));
+/// 核心备份执行函数，将 `backup_paths` 作为一个快照备份，返回 restic 输出的备份汇总
+fn execute_backup(restic_exe_path: &str, repo_path: &Path, backup_paths: &[&Path], passwd: &PasswordSource, options: &BackupOptions) -> Result<BackupSummary, Error> {
+    let client = ResticClient::new(restic_exe_path, repo_path.to_string_lossy(), passwd.clone()).with_unlock_policy(options.unlock.clone());
+
+    // 1. 如果仓库不存在，则自动初始化 (远程仓库由 restic 探测，不检查本地目录)
+    let remote = utils::is_remote_repo(client.repo());
+    if !with_spinner(options.output, || client.is_initialized())? {
+        if !remote {
+            if repo_path.exists() {
+                let mut entries = repo_path
+                    .read_dir()
+                    .map_err(|e| Error::RepoNotFound(format!("{} 已存在但无法作为仓库目录读取: {}", repo_path.display(), e)))?;
+                if entries.next().is_some() {
+                    return Err(Error::RepoNotFound(format!("目录 {} 已存在但不是有效的 restic 仓库", repo_path.display())));
+                }
+            }
+            fs::create_dir_all(repo_path).map_err(|e| format!("创建仓库目录失败: {}", e))?;
+        }
+
+        options.output.println(format!("{} 仓库 {} 不存在，正在初始化...", style("i").blue(), repo_path.display()));
+        if let Some(source) = &options.init.copy_chunker_params_from {
+            options.output.println(format!("{} 从仓库 {} 复制分块参数。", style("i").blue(), source.repo));
         }
-        fs::create_dir_all(repo_path).map_err(|e| format!("创建仓库目录失败: {}", e))?;
-        
-        println!("{} 仓库 {} 不存在，正在初始化...", style("i").blue(), repo_path.display());
         // init 时不强制指定 pack-size，留给 backup 命令指定
-        let init_args = ["-r", &repo_path.to_string_lossy(), "init"];
-        run_restic_command(restic_exe_path, &init_args, passwd)?;
-        println!("{} 仓库初始化成功。", style("✔").green());
+        with_spinner(options.output, || client.init_with(options.init))?;
+        options.output.println(format!("{} 仓库初始化成功。", style("✔").green()));
     }
 
-    // 2. 执行备份
-    let repo_path_str = repo_path.to_string_lossy();
-    let backup_path_str = backup_path.to_string_lossy();
-    let pack_size_str = pack_size.to_string();
-    
-    let mut backup_args = vec![
-        "-r", &repo_path_str,
-        "backup", &backup_path_str,
-        "--no-scan",
-        "--pack-size", &pack_size_str // 在备份时指定 pack-size
-    ];
-
-    if !tag.is_empty() {
-        backup_args.push("--tag");
-        backup_args.push(tag);
-    }
-    
-    println!("{} 开始执行备份...", style("i").blue());
-    run_restic_command(restic_exe_path, &backup_args, passwd)
+    // 2. 格式 v1 的仓库不支持压缩: 要求 max 时报错，auto/off 时不传 --compression
+    let compression = match options.compression {
+        Some(compression) if cached_repo_version(&client, options)? < 2 => {
+            if compression == Compression::Max {
+                return Err(format!(
+                    "仓库 {} 为格式 v1，不支持压缩 (compression = \"max\")；请改为 \"auto\" / \"off\"，或执行 `restic migrate upgrade_repo_v2` 升级仓库。",
+                    repo_path.display()
+                ).into());
+            }
+            if compression == Compression::Auto {
+                options.output.println(format!("{} 仓库为格式 v1，不支持压缩，本次备份不压缩。", style("i").blue()));
+            }
+            None
+        }
+        other => other,
+    };
+
+    // 3. 执行备份
+    let request = BackupRequest {
+        paths: backup_paths.iter().map(|p| p.to_path_buf()).collect(),
+        tag: options.tag.to_string(),
+        pack_size: Some(options.pack_size), // 在备份时指定 pack-size
+        compression,
+        exclude: options.exclude.clone(),
+    };
+    options.output.println(format!("{} 开始执行备份...", style("i").blue()));
+    let progress = BackupProgress::start(options.output);
+    let result = client.backup(&request, |event| progress.handle(event));
+    progress.finish();
+    let summary = result?;
+    options.bytes_added.set(Some(options.bytes_added.get().unwrap_or(0) + summary.data_added));
+    Ok(summary)
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use crate::client::ResticClient;
+use crate::config::PasswordSource;
+use crate::error::Error;
+use crate::progress::{with_spinner, JobOutput};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// `restic ls --json` 输出的文件/目录节点
+#[derive(Debug, Clone, Deserialize)]
+pub struct Node {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    /// 在快照中的完整路径 (restic 格式)
+    pub path: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+impl Node {
+    pub fn is_dir(&self) -> bool {
+        self.node_type == "dir"
+    }
+}
+
+/// 快照文件树浏览器，按需逐级调用 `restic ls` 读取目录内容并缓存
+pub struct SnapshotBrowser<'a> {
+    restic_exe_path: &'a str,
+    repo_path: &'a str,
+    password: &'a PasswordSource,
+    snapshot_id: &'a str,
+    cache: HashMap<String, Vec<Node>>,
+}
+
+impl<'a> SnapshotBrowser<'a> {
+    pub fn new(restic_exe_path: &'a str, repo_path: &'a str, password: &'a PasswordSource, snapshot_id: &'a str) -> Self {
+        SnapshotBrowser {
+            restic_exe_path,
+            repo_path,
+            password,
+            snapshot_id,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// 列出目录下的直接子项 (目录在前，按名称排序)
+    pub fn list_dir(&mut self, dir: &str) -> Result<&[Node], Error> {
+        if !self.cache.contains_key(dir) {
+            // 指定目录时 restic ls 默认不递归，只返回该目录本身及其直接子项
+            let client = ResticClient::new(self.restic_exe_path, self.repo_path, self.password.clone());
+            let output = with_spinner(&JobOutput::default(), || client.run(&["ls", "--json", self.snapshot_id, dir]))?;
+
+            let mut nodes: Vec<Node> = output
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Node>(line).ok())
+                .filter(|node| node.path != dir)
+                .collect();
+            nodes.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.name.cmp(&b.name)));
+            self.cache.insert(dir.to_string(), nodes);
+        }
+        Ok(&self.cache[dir])
+    }
+}
+
+/// 上一级目录，根目录的上一级仍是根目录
+pub fn parent_dir(dir: &str) -> String {
+    match dir.trim_end_matches('/').rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => dir[..i].to_string(),
+    }
+}
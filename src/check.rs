@@ -0,0 +1,25 @@
+use crate::client::ResticClient;
+use crate::config::PasswordSource;
+use crate::error::Error;
+use crate::lock::UnlockPolicy;
+use crate::progress::{with_spinner, JobOutput};
+use console::style;
+use regex::Regex;
+
+/// 对仓库执行 `restic check`
+///
+/// `read_data_subset` 不为空时追加 `--read-data-subset`，同时校验对应比例的数据包内容。
+pub fn run_check(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, read_data_subset: Option<&str>, unlock: UnlockPolicy, output: &JobOutput) -> Result<String, Error> {
+    match read_data_subset {
+        Some(subset) => output.println(format!("{} 正在校验仓库 {} (读取数据: {}) ...", style("i").blue(), repo_path, subset)),
+        None => output.println(format!("{} 正在校验仓库 {} ...", style("i").blue(), repo_path)),
+    }
+    let client = ResticClient::new(restic_exe_path, repo_path, password.clone()).with_unlock_policy(unlock);
+    with_spinner(output, || client.check(read_data_subset))
+}
+
+/// 检查 `--read-data-subset` 的取值格式: "n/t"、"x%" 或大小 (如 "2G")
+pub fn is_valid_data_subset(subset: &str) -> bool {
+    let re = Regex::new(r"^(\d+/\d+|\d+(\.\d+)?%|\d+[KMGTkmgt]?)$").unwrap();
+    re.is_match(subset.trim())
+}
@@ -0,0 +1,448 @@
+use console::style;
+use xcompress::archive::ArchiveFormat;
+use xcompress::config::PasswordSource;
+use xcompress::history::HistoryFilter;
+
+/// 命令行解析结果
+#[derive(Debug)]
+pub struct Cli {
+    pub command: CliCommand,
+    /// `--yes` / `--no-pause`: 不弹出任何交互提示，结束时也不等待回车
+    pub no_pause: bool,
+    /// `--report json`: 运行结束后输出机器可读的 JSON 报告
+    pub report_json: bool,
+    /// `--report-file <文件>`: 将 JSON 报告写入文件而不是标准输出 (隐含 `--report json`)
+    pub report_file: Option<String>,
+}
+
+impl Cli {
+    /// 是否向标准输出打印 JSON (运行报告或 `diff` 的结果)，此时标准输出只能包含 JSON
+    pub fn json_on_stdout(&self) -> bool {
+        (self.report_json && self.report_file.is_none()) || matches!(&self.command, CliCommand::Diff(args) if args.output.is_none())
+    }
+}
+
+/// 支持的子命令
+#[derive(Debug)]
+pub enum CliCommand {
+    /// 无参数: 显示交互式主菜单
+    Menu,
+    /// 旧版用法: 直接传入一个路径或 toml 文件，由程序猜测意图
+    Legacy(String),
+    Backup(BackupArgs),
+    Restore(RestoreArgs),
+    /// `jobs`: `--jobs N`，覆盖配置文件中的 `max_parallel`
+    BatchBackup { config: Option<String>, jobs: Option<usize> },
+    BatchRestore { config: Option<String> },
+    Snapshots(RepoArgs),
+    Check(CheckArgs),
+    Diff(DiffArgs),
+    History(HistoryArgs),
+    /// 常驻运行，按配置中的 `schedule` 定时备份
+    Daemon { config: String, jobs: Option<usize> },
+    Help,
+}
+
+impl CliCommand {
+    /// 子命令名称，用于运行报告
+    pub fn name(&self) -> &'static str {
+        match self {
+            CliCommand::Menu => "menu",
+            CliCommand::Legacy(_) => "legacy",
+            CliCommand::Backup(_) => "backup",
+            CliCommand::Restore(_) => "restore",
+            CliCommand::BatchBackup { .. } => "batch-backup",
+            CliCommand::BatchRestore { .. } => "batch-restore",
+            CliCommand::Snapshots(_) => "snapshots",
+            CliCommand::Check(_) => "check",
+            CliCommand::Diff(_) => "diff",
+            CliCommand::History(_) => "history",
+            CliCommand::Daemon { .. } => "daemon",
+            CliCommand::Help => "help",
+        }
+    }
+
+    /// 是否按 TOML 配置文件执行任务 (任务可以通过 `restic_binary` 自带 restic)
+    pub fn reads_config(&self) -> bool {
+        matches!(self, CliCommand::BatchBackup { .. } | CliCommand::BatchRestore { .. } | CliCommand::Daemon { .. })
+    }
+}
+
+/// 仓库与密码相关的通用参数
+#[derive(Debug, Default)]
+pub struct RepoArgs {
+    pub repo: Option<String>,
+    pub passwd: Option<PasswordSource>,
+}
+
+#[derive(Debug, Default)]
+pub struct BackupArgs {
+    pub paths: Vec<String>,
+    pub repo_args: RepoArgs,
+    pub tag: Option<String>,
+    pub pack_size: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct CheckArgs {
+    pub repo_args: RepoArgs,
+    pub read_data_subset: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DiffArgs {
+    pub repo_args: RepoArgs,
+    /// 较旧的快照 (比较起点)
+    pub from: String,
+    /// 较新的快照 (比较终点)
+    pub to: String,
+    /// `--path`: 只输出匹配该路径的变化
+    pub path: Option<String>,
+    /// `-o` / `--output`: 将 JSON 写入文件而不是标准输出
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct HistoryArgs {
+    pub filter: HistoryFilter,
+    /// `--details`: 打印每条记录的全部信息
+    pub details: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreArgs {
+    pub repo_args: RepoArgs,
+    pub target: Option<String>,
+    pub snapshot: Option<String>,
+    pub restore_path: Option<String>,
+    /// `--format`: 在目标目录中生成归档文件而不是恢复为普通文件
+    pub format: Option<ArchiveFormat>,
+}
+
+/// 解析命令行参数 (不包含程序名本身)
+///
+/// 第一个参数若不是已知的子命令，则按旧版的“路径猜测”方式处理，保持拖拽启动的兼容性。
+pub fn parse_args(args: &[String]) -> Result<Cli, String> {
+    let mut no_pause = false;
+    let mut report_json = false;
+    let mut report_file = None;
+    let mut rest: Vec<String> = Vec::new();
+    let mut global = ArgParser::new(args);
+    while let Some(arg) = global.next() {
+        match arg.as_str() {
+            "-y" | "--yes" | "--no-pause" => no_pause = true,
+            "--report" => match global.value(&arg)?.as_str() {
+                "json" => report_json = true,
+                other => return Err(format!("--report 目前仅支持 json，当前值: {}", other)),
+            },
+            "--report-file" => {
+                report_json = true;
+                report_file = Some(global.value(&arg)?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    let Some(first) = rest.first() else {
+        return Ok(Cli { command: CliCommand::Menu, no_pause, report_json, report_file });
+    };
+
+    let mut parser = ArgParser::new(&rest[1..]);
+    let command = match first.as_str() {
+        "backup" => {
+            let mut backup = BackupArgs::default();
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-r" | "--repo" => backup.repo_args.repo = Some(parser.value(&arg)?),
+                    "--tag" => backup.tag = Some(parser.value(&arg)?),
+                    "--pack-size" => backup.pack_size = Some(parse_pack_size(&parser.value(&arg)?)?),
+                    _ if parse_passwd_flag(&arg, &mut parser, &mut backup.repo_args)? => {}
+                    _ if arg.starts_with('-') => return Err(format!("backup: 未知参数 '{}'", arg)),
+                    _ => backup.paths.push(arg),
+                }
+            }
+            CliCommand::Backup(backup)
+        }
+        "restore" => {
+            let mut restore = RestoreArgs::default();
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-r" | "--repo" => restore.repo_args.repo = Some(parser.value(&arg)?),
+                    "-t" | "--target" => restore.target = Some(parser.value(&arg)?),
+                    "-s" | "--snapshot" => restore.snapshot = Some(parser.value(&arg)?),
+                    "--path" => restore.restore_path = Some(parser.value(&arg)?),
+                    "--format" => restore.format = Some(ArchiveFormat::parse(&parser.value(&arg)?)?),
+                    _ if parse_passwd_flag(&arg, &mut parser, &mut restore.repo_args)? => {}
+                    _ if arg.starts_with('-') => return Err(format!("restore: 未知参数 '{}'", arg)),
+                    // 允许将仓库路径作为位置参数传入
+                    _ if restore.repo_args.repo.is_none() => restore.repo_args.repo = Some(arg),
+                    _ => return Err(format!("restore: 多余的参数 '{}'", arg)),
+                }
+            }
+            CliCommand::Restore(restore)
+        }
+        "batch-backup" => {
+            let mut config = None;
+            let mut jobs = None;
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-j" | "--jobs" => jobs = Some(parse_jobs(&parser.value(&arg)?)?),
+                    _ if arg.starts_with('-') => return Err(format!("batch-backup: 未知参数 '{}'", arg)),
+                    _ if config.is_none() => config = Some(arg),
+                    _ => return Err(format!("batch-backup: 多余的参数 '{}'", arg)),
+                }
+            }
+            CliCommand::BatchBackup { config, jobs }
+        }
+        "batch-restore" => CliCommand::BatchRestore { config: parser.single_positional("batch-restore")? },
+        "snapshots" => CliCommand::Snapshots(parser.repo_args("snapshots")?),
+        "check" => {
+            let mut check = CheckArgs::default();
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-r" | "--repo" => check.repo_args.repo = Some(parser.value(&arg)?),
+                    "--read-data-subset" => check.read_data_subset = Some(parser.value(&arg)?),
+                    _ if parse_passwd_flag(&arg, &mut parser, &mut check.repo_args)? => {}
+                    _ if !arg.starts_with('-') && check.repo_args.repo.is_none() => check.repo_args.repo = Some(arg),
+                    _ => return Err(format!("check: 未知参数 '{}'", arg)),
+                }
+            }
+            if let Some(subset) = &check.read_data_subset {
+                if !xcompress::check::is_valid_data_subset(subset) {
+                    return Err(format!("--read-data-subset 格式无效: '{}' (示例: 5%、1/10、2G)", subset));
+                }
+            }
+            CliCommand::Check(check)
+        }
+        "diff" => {
+            let mut diff = DiffArgs::default();
+            let mut snapshots = Vec::new();
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-r" | "--repo" => diff.repo_args.repo = Some(parser.value(&arg)?),
+                    "--path" => diff.path = Some(parser.value(&arg)?),
+                    "-o" | "--output" => diff.output = Some(parser.value(&arg)?),
+                    _ if parse_passwd_flag(&arg, &mut parser, &mut diff.repo_args)? => {}
+                    _ if arg.starts_with('-') => return Err(format!("diff: 未知参数 '{}'", arg)),
+                    _ => snapshots.push(arg),
+                }
+            }
+            let [from, to] = <[String; 2]>::try_from(snapshots)
+                .map_err(|_| "diff: 需要提供两个快照 ID，例如 `xcompress diff -r D:\\repo --passwd-env PW 1a2b3c4d latest`。")?;
+            diff.from = from;
+            diff.to = to;
+            CliCommand::Diff(diff)
+        }
+        "daemon" => {
+            let mut config = None;
+            let mut jobs = None;
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "-j" | "--jobs" => jobs = Some(parse_jobs(&parser.value(&arg)?)?),
+                    _ if arg.starts_with('-') => return Err(format!("daemon: 未知参数 '{}'", arg)),
+                    _ if config.is_none() => config = Some(arg),
+                    _ => return Err(format!("daemon: 多余的参数 '{}'", arg)),
+                }
+            }
+            let config = config.ok_or("daemon: 需要提供配置文件路径，例如 `xcompress daemon backup_config.toml`。")?;
+            CliCommand::Daemon { config, jobs }
+        }
+        "history" => {
+            let mut history = HistoryArgs::default();
+            while let Some(arg) = parser.next() {
+                match arg.as_str() {
+                    "--job" => history.filter.job = Some(parser.value(&arg)?),
+                    "--kind" => match parser.value(&arg)?.as_str() {
+                        kind @ ("backup" | "restore") => history.filter.kind = Some(kind.to_string()),
+                        other => return Err(format!("--kind 只能是 backup 或 restore，当前值: {}", other)),
+                    },
+                    "--success" => history.filter.success = Some(true),
+                    "--failed" => history.filter.success = Some(false),
+                    "-n" | "--limit" => {
+                        let value = parser.value(&arg)?;
+                        history.filter.limit = Some(value.parse().map_err(|_| format!("--limit 必须是整数，当前值: {}", value))?);
+                    }
+                    "--details" => history.details = true,
+                    // 允许将任务键名作为位置参数传入
+                    _ if !arg.starts_with('-') && history.filter.job.is_none() => history.filter.job = Some(arg),
+                    _ => return Err(format!("history: 未知参数 '{}'", arg)),
+                }
+            }
+            CliCommand::History(history)
+        }
+        "help" | "-h" | "--help" => CliCommand::Help,
+        _ => {
+            if rest.len() > 1 {
+                return Err(format!("未知的子命令 '{}'，使用 `xcompress help` 查看用法。", first));
+            }
+            CliCommand::Legacy(first.clone())
+        }
+    };
+
+    // 标准输出只能容纳一个 JSON 文档
+    if report_json && report_file.is_none() && matches!(&command, CliCommand::Diff(args) if args.output.is_none()) {
+        return Err("diff 未指定 --output 时差异已输出到标准输出，不能同时使用 --report json，请指定 --output 或 --report-file。".to_string());
+    }
+
+    Ok(Cli { command, no_pause, report_json, report_file })
+}
+
+/// 解析密码相关参数，返回 `true` 表示该参数已被处理
+fn parse_passwd_flag(arg: &str, parser: &mut ArgParser, repo_args: &mut RepoArgs) -> Result<bool, String> {
+    let passwd = match arg {
+        "--passwd" | "--password" => {
+            eprintln!("{} {} 会让密码出现在进程列表与 shell 历史中，建议改用 --passwd-env 或 --passwd-file。", style("!").yellow(), arg);
+            PasswordSource::Plain(parser.value(arg)?)
+        }
+        "--passwd-file" | "--password-file" => PasswordSource::File(parser.value(arg)?),
+        "--passwd-command" | "--password-command" => PasswordSource::Command(parser.value(arg)?),
+        "--passwd-env" | "--password-env" => PasswordSource::Env(parser.value(arg)?),
+        _ => return Ok(false),
+    };
+    repo_args.passwd = Some(passwd);
+    Ok(true)
+}
+
+fn parse_pack_size(value: &str) -> Result<u64, String> {
+    let size: u64 = value.parse().map_err(|_| format!("--pack-size 必须是整数，当前值: {}", value))?;
+    if !(16..=128).contains(&size) {
+        return Err(format!("--pack-size 必须在 16 到 128 (MiB) 之间，当前值: {}", size));
+    }
+    Ok(size)
+}
+
+fn parse_jobs(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("--jobs 必须是大于 0 的整数，当前值: {}", value)),
+    }
+}
+
+struct ArgParser<'a> {
+    args: std::slice::Iter<'a, String>,
+}
+
+impl<'a> ArgParser<'a> {
+    fn new(args: &'a [String]) -> Self {
+        Self { args: args.iter() }
+    }
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next().cloned()
+    }
+
+    fn value(&mut self, flag: &str) -> Result<String, String> {
+        self.next().ok_or_else(|| format!("参数 '{}' 缺少取值。", flag))
+    }
+
+    fn single_positional(&mut self, cmd: &str) -> Result<Option<String>, String> {
+        let value = self.next();
+        if let Some(extra) = self.next() {
+            return Err(format!("{}: 多余的参数 '{}'", cmd, extra));
+        }
+        Ok(value)
+    }
+
+    fn repo_args(&mut self, cmd: &str) -> Result<RepoArgs, String> {
+        let mut repo_args = RepoArgs::default();
+        while let Some(arg) = self.next() {
+            match arg.as_str() {
+                "-r" | "--repo" => repo_args.repo = Some(self.value(&arg)?),
+                _ if parse_passwd_flag(&arg, self, &mut repo_args)? => {}
+                _ if !arg.starts_with('-') && repo_args.repo.is_none() => repo_args.repo = Some(arg),
+                _ => return Err(format!("{}: 未知参数 '{}'", cmd, arg)),
+            }
+        }
+        Ok(repo_args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_global_flags_anywhere() {
+        let cli = parse(&["backup", "--yes", "-r", "/repo", "--passwd-env", "PW", "/data", "--report-file", "out.json"]).unwrap();
+        assert!(cli.no_pause);
+        assert!(cli.report_json);
+        assert_eq!(cli.report_file.as_deref(), Some("out.json"));
+        assert!(!cli.json_on_stdout());
+        let CliCommand::Backup(backup) = cli.command else { panic!("不是 backup") };
+        assert_eq!(backup.paths, ["/data"]);
+        assert_eq!(backup.repo_args.repo.as_deref(), Some("/repo"));
+        assert!(matches!(backup.repo_args.passwd, Some(PasswordSource::Env(ref name)) if name == "PW"));
+
+        assert!(matches!(parse(&[]).unwrap().command, CliCommand::Menu));
+        assert!(parse(&["--report", "json"]).unwrap().json_on_stdout());
+        assert!(parse(&["--report", "xml", "snapshots"]).is_err());
+    }
+
+    #[test]
+    fn parses_backup_and_restore() {
+        let CliCommand::Backup(backup) = parse(&["backup", "a", "b", "--tag", "daily", "--pack-size", "64"]).unwrap().command else { panic!("不是 backup") };
+        assert_eq!(backup.paths, ["a", "b"]);
+        assert_eq!(backup.tag.as_deref(), Some("daily"));
+        assert_eq!(backup.pack_size, Some(64));
+        assert!(parse(&["backup", "a", "--pack-size", "8"]).is_err());
+        assert!(parse(&["backup", "a", "--pack-size", "big"]).is_err());
+        assert!(parse(&["backup", "a", "--bogus"]).is_err());
+
+        let CliCommand::Restore(restore) = parse(&["restore", "/repo", "-t", "/out", "-s", "1a2b3c4d", "--format", "zip", "--passwd-file", "/pw"]).unwrap().command else { panic!("不是 restore") };
+        assert_eq!(restore.repo_args.repo.as_deref(), Some("/repo"));
+        assert_eq!(restore.target.as_deref(), Some("/out"));
+        assert_eq!(restore.snapshot.as_deref(), Some("1a2b3c4d"));
+        assert_eq!(restore.format, Some(ArchiveFormat::Zip));
+        assert!(matches!(restore.repo_args.passwd, Some(PasswordSource::File(_))));
+        assert!(parse(&["restore", "/repo", "/other"]).is_err());
+        assert!(parse(&["restore", "--target"]).unwrap_err().contains("缺少取值"));
+    }
+
+    #[test]
+    fn parses_batch_commands() {
+        assert!(matches!(parse(&["batch-backup", "a.toml", "-j", "3"]).unwrap().command, CliCommand::BatchBackup { config: Some(ref c), jobs: Some(3) } if c == "a.toml"));
+        assert!(matches!(parse(&["batch-backup"]).unwrap().command, CliCommand::BatchBackup { config: None, jobs: None }));
+        assert!(parse(&["batch-backup", "-j", "0"]).is_err());
+        assert!(parse(&["batch-restore", "a.toml", "b.toml"]).is_err());
+        assert!(matches!(parse(&["daemon", "a.toml", "--jobs", "2"]).unwrap().command, CliCommand::Daemon { jobs: Some(2), .. }));
+        assert!(parse(&["daemon"]).is_err());
+    }
+
+    #[test]
+    fn parses_check_diff_and_history() {
+        let CliCommand::Check(check) = parse(&["check", "/repo", "--read-data-subset", "5%"]).unwrap().command else { panic!("不是 check") };
+        assert_eq!(check.read_data_subset.as_deref(), Some("5%"));
+        assert!(parse(&["check", "/repo", "--read-data-subset", "lots"]).is_err());
+
+        let cli = parse(&["diff", "-r", "/repo", "aaaa", "latest", "--path", "/data"]).unwrap();
+        assert!(cli.json_on_stdout());
+        let CliCommand::Diff(diff) = cli.command else { panic!("不是 diff") };
+        assert_eq!((diff.from.as_str(), diff.to.as_str()), ("aaaa", "latest"));
+        assert_eq!(diff.path.as_deref(), Some("/data"));
+        assert!(parse(&["diff", "-r", "/repo", "aaaa"]).is_err());
+        // 差异与报告不能同时写到标准输出
+        assert!(parse(&["--report", "json", "diff", "aaaa", "bbbb"]).is_err());
+        assert!(parse(&["--report", "json", "diff", "aaaa", "bbbb", "-o", "d.json"]).is_ok());
+        assert!(parse(&["--report-file", "r.json", "diff", "aaaa", "bbbb"]).is_ok());
+
+        let CliCommand::History(history) = parse(&["history", "project_A", "--kind", "backup", "--failed", "-n", "5", "--details"]).unwrap().command else { panic!("不是 history") };
+        assert_eq!(history.filter.job.as_deref(), Some("project_A"));
+        assert_eq!(history.filter.kind.as_deref(), Some("backup"));
+        assert_eq!(history.filter.success, Some(false));
+        assert_eq!(history.filter.limit, Some(5));
+        assert!(history.details);
+        assert!(parse(&["history", "--kind", "check"]).is_err());
+        assert!(parse(&["history", "-n", "many"]).is_err());
+    }
+
+    #[test]
+    fn keeps_legacy_single_path_usage() {
+        assert!(matches!(parse(&["D:\\backup_config.toml"]).unwrap().command, CliCommand::Legacy(ref p) if p == "D:\\backup_config.toml"));
+        assert!(matches!(parse(&["help"]).unwrap().command, CliCommand::Help));
+        assert!(parse(&["frobnicate", "x"]).unwrap_err().contains("未知的子命令"));
+    }
+}
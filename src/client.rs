@@ -0,0 +1,605 @@
+use crate::config::{ExcludeOptions, PasswordSource, RetentionPolicy};
+use crate::progress::{BackupEvent, BackupStatus, BackupSummary};
+use crate::error::{Error, ErrorKind};
+use crate::lock::{self, UnlockPolicy};
+use crate::utils::{self, format_duration, spawn_restic};
+use crate::version::{self, ResticVersion};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// 对单个 restic 仓库执行操作的客户端
+///
+/// 不打印任何内容、不弹出交互提示；备份进度通过回调报告，其余操作的结果以返回值给出。
+/// 交互式菜单与批量任务都建立在它之上，其它程序也可以直接嵌入使用。
+///
+/// 所有操作在仓库被锁定时都按 `with_unlock_policy` 设置的策略处理，默认不移除锁。
+#[derive(Debug, Clone)]
+pub struct ResticClient {
+    restic_exe_path: String,
+    repo: String,
+    password: PasswordSource,
+    unlock: UnlockPolicy,
+}
+
+/// `ResticClient::backup` 的参数
+#[derive(Debug, Clone, Default)]
+pub struct BackupRequest {
+    /// 作为同一个快照备份的路径
+    pub paths: Vec<PathBuf>,
+    /// 快照标签，多个标签以逗号分隔 (同 restic `--tag`)
+    pub tag: String,
+    /// 数据包大小 (MiB)，`None` 时使用 restic 默认值
+    pub pack_size: Option<u64>,
+    /// 压缩模式，`None` 时使用 restic 默认值 (auto)
+    pub compression: Option<Compression>,
+    pub exclude: ExcludeOptions,
+}
+
+/// `ResticClient::init_with` 的参数，只在新建仓库时生效
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    /// 仓库格式版本: "1"、"2"、"latest" 或 "stable" (同 restic `--repository-version`)，`None` 时使用 restic 默认值
+    pub repo_version: Option<String>,
+    /// 从该仓库复制分块参数，使两个仓库之间的 `restic copy` 可以复用已有的数据块
+    pub copy_chunker_params_from: Option<SourceRepo>,
+}
+
+/// 复制分块参数时的来源仓库
+#[derive(Debug, Clone)]
+pub struct SourceRepo {
+    pub repo: String,
+    pub password: PasswordSource,
+}
+
+/// restic 的压缩模式 (`--compression`)，只对格式 v2 的仓库有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// 自动，兼顾速度与压缩率 (restic 默认)
+    Auto,
+    /// 最高压缩率，速度较慢
+    Max,
+    /// 不压缩
+    Off,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Compression::Auto, Compression::Max, Compression::Off];
+
+    /// 解析 `compression` 字段: "auto"、"max" 或 "off"
+    pub fn parse(value: &str) -> Result<Compression, String> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Ok(Compression::Auto),
+            "max" => Ok(Compression::Max),
+            "off" => Ok(Compression::Off),
+            other => Err(format!("不支持的压缩模式 '{}' (可选: auto、max、off)", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Compression::Auto => "auto",
+            Compression::Max => "max",
+            Compression::Off => "off",
+        }
+    }
+}
+
+/// `ResticClient::restore` 的参数
+#[derive(Debug, Clone, Default)]
+pub struct RestoreRequest {
+    /// 快照 ID，可以带子路径 ("id:/path") 只恢复该目录的内容
+    pub snapshot: String,
+    pub target: PathBuf,
+    /// 只恢复这些路径 (同 restic `--include`)，为空时恢复全部
+    pub include: Vec<String>,
+}
+
+/// 仓库中的一个快照
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub short_id: String,
+    pub time: String,
+    pub paths: Vec<String>,
+    pub size: u64,
+}
+
+/// 仓库中的一个锁 (`restic cat lock`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockInfo {
+    /// 锁文件 ID
+    #[serde(skip_deserializing)]
+    pub id: String,
+    /// 创建或最后一次刷新的时间 (RFC 3339)，持有锁的 restic 进程会定期刷新
+    pub time: String,
+    pub exclusive: bool,
+    pub hostname: String,
+    pub username: String,
+    pub pid: u64,
+}
+
+impl LockInfo {
+    /// 距最后一次刷新经过的时间，时间无法解析时返回 `None`
+    pub fn age(&self) -> Option<Duration> {
+        let time = DateTime::parse_from_rfc3339(&self.time).ok()?;
+        (Local::now().fixed_offset() - time).to_std().ok()
+    }
+
+    /// 锁文件 ID 的前 8 位
+    pub fn short_id(&self) -> &str {
+        self.id.get(..8).unwrap_or(&self.id)
+    }
+
+    /// 单行的人类可读描述
+    pub fn describe(&self) -> String {
+        let age = self.age().map_or_else(|| self.time.clone(), |age| format!("{}前", format_duration(age)));
+        format!(
+            "{} {}: {} 上的进程 {} (用户 {})，最后刷新于 {}",
+            if self.exclusive { "独占锁" } else { "共享锁" },
+            self.short_id(),
+            self.hostname,
+            self.pid,
+            self.username,
+            age
+        )
+    }
+}
+
+/// `restic backup --json` 输出的一行消息
+#[derive(Debug, Deserialize)]
+#[serde(tag = "message_type", rename_all = "snake_case")]
+enum BackupMessage {
+    Status(BackupStatus),
+    Summary(BackupSummary),
+    Error(BackupError),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BackupError {
+    error: BackupErrorDetail,
+    item: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BackupErrorDetail {
+    message: String,
+}
+
+/// 将标准错误线程解析出的文件错误转交给 `on_event`，返回转交的数量
+fn forward_errors(errors: &mpsc::Receiver<BackupError>, on_event: &mut impl FnMut(BackupEvent)) -> u64 {
+    let mut count = 0;
+    for e in errors.try_iter() {
+        on_event(BackupEvent::Error { item: e.item, message: e.error.message });
+        count += 1;
+    }
+    count
+}
+
+/// `restic forget --json` 输出中的一个快照分组
+#[derive(Debug, Deserialize)]
+struct ForgetGroup {
+    #[serde(default)]
+    remove: Option<Vec<ForgetSnapshot>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForgetSnapshot {
+    short_id: String,
+}
+
+impl ResticClient {
+    pub fn new(restic_exe_path: impl Into<String>, repo: impl Into<String>, password: PasswordSource) -> Self {
+        Self { restic_exe_path: restic_exe_path.into(), repo: repo.into(), password, unlock: UnlockPolicy::Never }
+    }
+
+    /// 设置仓库被锁定时的处理方式
+    pub fn with_unlock_policy(mut self, policy: UnlockPolicy) -> Self {
+        self.unlock = policy;
+        self
+    }
+
+    /// 自动查找 restic (程序目录优先，其次系统 PATH) 并创建客户端，版本过旧时返回 `Error::ResticTooOld`
+    pub fn locate(repo: impl Into<String>, password: PasswordSource) -> Result<Self, Error> {
+        let restic_exe_path = utils::find_restic().ok_or(Error::ResticMissing)?;
+        if let Some(v) = version::detect(&restic_exe_path) {
+            v.ensure_supported()?;
+        }
+        Ok(Self::new(restic_exe_path, repo, password))
+    }
+
+    /// restic 的版本 (只检测一次)，无法识别时返回 `None`
+    pub fn version(&self) -> Option<ResticVersion> {
+        version::detect(&self.restic_exe_path)
+    }
+
+    pub fn restic_exe_path(&self) -> &str {
+        &self.restic_exe_path
+    }
+
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    /// 对仓库执行任意 restic 子命令 (自动加上 `-r <仓库>`)，返回标准输出
+    pub fn run(&self, args: &[&str]) -> Result<String, Error> {
+        self.retry_if_locked(|| self.run_once(args))
+    }
+
+    /// 同 `run`，但不处理仓库锁 (用于查看与移除锁本身)
+    fn run_once(&self, args: &[&str]) -> Result<String, Error> {
+        let mut full_args = vec!["-r", self.repo.as_str()];
+        full_args.extend_from_slice(args);
+        utils::run_restic(&self.restic_exe_path, &full_args, &self.password)
+    }
+
+    /// 执行 `op`，若因仓库被锁定而失败，按解锁策略决定是否移除这些锁后重试一次
+    ///
+    /// 不移除时错误信息中列出持有锁的进程；无法列出仓库锁时返回该错误。
+    /// `run` 与 `backup` 已经包含这一处理，直接启动 restic 的调用方 (如 `restic dump`) 可以用它包装。
+    pub fn retry_if_locked<T>(&self, mut op: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let error = match op() {
+            Err(e) if e.kind() == ErrorKind::RepoLocked => e,
+            other => return other,
+        };
+
+        let locks = self.locks().map_err(|e| e.with_message(|e| format!("仓库已被锁定，且无法列出仓库锁: {}", e)))?;
+        if locks.is_empty() {
+            // 锁在此期间已被释放
+            return op();
+        }
+        if !self.unlock.should_unlock(&locks) {
+            return Err(error.with_message(|e| format!("{} 持有锁: {}", e, lock::holders(&locks))));
+        }
+        self.remove_locks(&locks).map_err(|e| e.with_message(|e| format!("移除仓库锁失败: {}", e)))?;
+        op()
+    }
+
+    /// 仓库是否已初始化: 本地路径检查目录结构，远程仓库通过 `restic cat config` 探测
+    pub fn is_initialized(&self) -> Result<bool, Error> {
+        if !utils::is_remote_repo(&self.repo) {
+            return Ok(utils::is_restic_repo(Path::new(&self.repo)));
+        }
+        match self.run(&["--no-lock", "cat", "config"]) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::RepoNotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 仓库格式版本 (`restic cat config`)，格式 v1 的仓库不支持压缩
+    pub fn repo_version(&self) -> Result<u32, Error> {
+        let output = self.run(&["--no-lock", "cat", "config"])?;
+        let config: Value = serde_json::from_str(&output).map_err(|e| format!("解析仓库配置失败: {}", e))?;
+        config["version"]
+            .as_u64()
+            .map(|v| v as u32)
+            .ok_or_else(|| "仓库配置中缺少 version 字段".into())
+    }
+
+    /// 初始化仓库
+    pub fn init(&self) -> Result<(), Error> {
+        self.init_with(&InitOptions::default())
+    }
+
+    /// 按 `options` 初始化仓库
+    ///
+    /// 来源仓库的密码不出现在命令行中: 明文与环境变量经 RESTIC_FROM_PASSWORD 传递，文件与命令交给 restic 自行读取。
+    pub fn init_with(&self, options: &InitOptions) -> Result<(), Error> {
+        let mut args = vec!["init"];
+        if let Some(version) = &options.repo_version {
+            args.push("--repository-version");
+            args.push(version);
+        }
+        let mut from_env = Vec::new();
+        if let Some(source) = &options.copy_chunker_params_from {
+            args.extend(["--from-repo", source.repo.as_str(), "--copy-chunker-params"]);
+            match &source.password {
+                PasswordSource::Plain(p) => from_env.push(("RESTIC_FROM_PASSWORD".to_string(), p.clone())),
+                PasswordSource::Env(var) => {
+                    let p = std::env::var(var).map_err(|_| format!("环境变量 '{}' 未设置。", var))?;
+                    from_env.push(("RESTIC_FROM_PASSWORD".to_string(), p));
+                }
+                PasswordSource::File(file) => args.extend(["--from-password-file", file.as_str()]),
+                PasswordSource::Command(cmd) => args.extend(["--from-password-command", cmd.as_str()]),
+            }
+        }
+        utils::with_restic_env(&from_env, || self.run(&args)).map(|_| ())
+    }
+
+    /// 备份 `request.paths` 为一个快照，进度与单个文件的错误通过 `on_event` 报告
+    pub fn backup(&self, request: &BackupRequest, mut on_event: impl FnMut(BackupEvent)) -> Result<BackupSummary, Error> {
+        self.retry_if_locked(|| self.backup_once(request, &mut on_event))
+    }
+
+    fn backup_once(&self, request: &BackupRequest, on_event: &mut impl FnMut(BackupEvent)) -> Result<BackupSummary, Error> {
+        let paths: Vec<String> = request.paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        let pack_size = request.pack_size.map(|size| size.to_string());
+        let exclude_args = request.exclude.to_args();
+        // 下面依赖 `--json` 的逐行进度输出，直接通过 `new` 创建的客户端也要在执行前检查版本
+        if let Some(v) = self.version() {
+            v.ensure_supported()?;
+        }
+
+        let mut args = vec![
+            "-r", self.repo.as_str(),
+            "backup",
+            "--json", // 逐行输出 JSON 进度
+        ];
+        if let Some(pack_size) = &pack_size {
+            args.push("--pack-size");
+            args.push(pack_size);
+        }
+        if let Some(compression) = request.compression {
+            args.push("--compression");
+            args.push(compression.as_str());
+        }
+        if !request.tag.is_empty() {
+            args.push("--tag");
+            args.push(&request.tag);
+        }
+        args.extend(exclude_args.iter().map(String::as_str));
+        // 路径放在所有选项之后，并以 `--` 分隔，避免以 '-' 开头的路径被当作参数
+        args.push("--");
+        args.extend(paths.iter().map(String::as_str));
+
+        let mut child = spawn_restic(&self.restic_exe_path, &args, &self.password)?;
+
+        // 标准错误单独在线程中读取，避免管道写满导致 restic 阻塞；
+        // restic 把单个文件的错误 (message_type 为 "error") 写到标准错误，经通道交给当前线程报告
+        let stderr_pipe = child.stderr.take().ok_or("无法读取 restic 标准错误输出")?;
+        let (error_tx, error_rx) = mpsc::channel();
+        let stderr_reader = thread::spawn(move || {
+            let mut other = String::new();
+            for line in BufReader::new(stderr_pipe).lines().map_while(Result::ok) {
+                match serde_json::from_str::<BackupMessage>(&line) {
+                    Ok(BackupMessage::Error(e)) => {
+                        let _ = error_tx.send(e);
+                    }
+                    _ => {
+                        other.push_str(&line);
+                        other.push('\n');
+                    }
+                }
+            }
+            other
+        });
+
+        let mut summary = None;
+        let mut read_errors = 0;
+        let mut other_output = String::new();
+        let stdout = child.stdout.take().ok_or("无法读取 restic 标准输出")?;
+        for line in BufReader::new(stdout).lines() {
+            let line = line.map_err(|e| format!("读取 restic 输出失败: {}", e))?;
+            read_errors += forward_errors(&error_rx, on_event);
+            match serde_json::from_str::<BackupMessage>(&line) {
+                Ok(BackupMessage::Status(status)) => on_event(BackupEvent::Status(status)),
+                Ok(BackupMessage::Summary(s)) => summary = Some(s),
+                Ok(BackupMessage::Error(e)) => {
+                    read_errors += 1;
+                    on_event(BackupEvent::Error { item: e.item, message: e.error.message });
+                }
+                Ok(BackupMessage::Other) => {}
+                Err(_) => {
+                    other_output.push_str(&line);
+                    other_output.push('\n');
+                }
+            }
+        }
+
+        let status = child.wait().map_err(|e| format!("等待 restic 进程失败: {}", e))?;
+        let stderr = stderr_reader.join().unwrap_or_default();
+        read_errors += forward_errors(&error_rx, on_event);
+
+        // 退出码 3: 快照已经生成，只是部分文件无法读取，按成功处理并在汇总中注明
+        let incomplete = status.code() == Some(3) && summary.is_some();
+        if !status.success() && !incomplete {
+            return Err(Error::from_restic_output(&other_output, &stderr));
+        }
+        let mut summary: BackupSummary = summary.ok_or_else(|| format!("restic 未输出备份汇总信息:\n{}\n{}", other_output, stderr))?;
+        summary.read_errors = read_errors;
+        Ok(summary)
+    }
+
+    /// 列出仓库中的快照，最新的在前
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, Error> {
+        let output = self.run(&["snapshots", "--json"])?;
+
+        // Restic 输出的 JSON 可能是格式化过的，包含换行符，需要跨行匹配 (dotall flag `(?s)`)
+        let re = Regex::new(r"(?s)\[.*\]").unwrap();
+        let json_str = re
+            .find(&output)
+            .map(|m| m.as_str())
+            .ok_or("无法从 restic 输出中解析快照 JSON 数据。")?;
+        let json_data: Value = serde_json::from_str(json_str)
+            .map_err(|e| format!("解析快照 JSON 失败: {}. Raw JSON string: {}", e, json_str))?;
+
+        let mut snapshots = Vec::new();
+        if let Some(snaps_array) = json_data.as_array() {
+            for snap in snaps_array {
+                snapshots.push(Snapshot {
+                    short_id: snap["short_id"].as_str().unwrap_or("").to_string(),
+                    time: snap["time"].as_str().unwrap_or("").to_string(),
+                    paths: snap["paths"].as_array().map_or(vec![], |paths| {
+                        paths.iter().map(|p| p.as_str().unwrap_or("").to_string()).collect()
+                    }),
+                    size: snap["size"].as_u64().unwrap_or(0),
+                });
+            }
+        }
+        snapshots.sort_by(|a, b| b.time.cmp(&a.time));
+        Ok(snapshots)
+    }
+
+    /// 恢复快照，返回 restic 的输出
+    pub fn restore(&self, request: &RestoreRequest) -> Result<String, Error> {
+        let target = request.target.to_string_lossy();
+        let mut args = vec!["restore", request.snapshot.as_str(), "--target", &target];
+        for p in &request.include {
+            args.push("--include");
+            args.push(p);
+        }
+        self.run(&args)
+    }
+
+    /// 按保留策略执行 `restic forget` (可选 `--prune`)，返回被移除的快照短 ID
+    pub fn forget(&self, policy: &RetentionPolicy) -> Result<Vec<String>, Error> {
+        let policy_args = policy.to_args();
+        let mut args = vec!["forget", "--json"];
+        args.extend(policy_args.iter().map(String::as_str));
+        let stdout = self.run(&args)?;
+
+        // 开启 --prune 时，JSON 结果之后还会输出 prune 的文本信息，只解析以 '[' 开头的那一行
+        let json_line = stdout
+            .lines()
+            .find(|line| line.trim_start().starts_with('['))
+            .ok_or_else(|| format!("无法从 restic 输出中解析 forget 结果:\n{}", stdout))?;
+        let groups: Vec<ForgetGroup> = serde_json::from_str(json_line)
+            .map_err(|e| format!("解析 forget JSON 失败: {}", e))?;
+
+        Ok(groups
+            .into_iter()
+            .flat_map(|g| g.remove.unwrap_or_default())
+            .map(|s| s.short_id)
+            .collect())
+    }
+
+    /// 列出仓库中当前的锁 (本身不加锁)
+    pub fn locks(&self) -> Result<Vec<LockInfo>, Error> {
+        let ids = self.run_once(&["--no-lock", "list", "locks"])?;
+        let mut locks = Vec::new();
+        for id in ids.lines().map(str::trim).filter(|id| !id.is_empty()) {
+            // 列出之后锁可能已被释放，读取失败的直接跳过
+            let Ok(content) = self.run_once(&["--no-lock", "cat", "lock", id]) else { continue };
+            let Ok(mut lock) = serde_json::from_str::<LockInfo>(&content) else { continue };
+            lock.id = id.to_string();
+            locks.push(lock);
+        }
+        Ok(locks)
+    }
+
+    /// 执行 `restic unlock`: 默认只移除 restic 判断为过期的锁，`remove_all` 时移除全部锁
+    pub fn unlock(&self, remove_all: bool) -> Result<(), Error> {
+        let mut args = vec!["unlock"];
+        if remove_all {
+            args.push("--remove-all");
+        }
+        self.run_once(&args).map(|_| ())
+    }
+
+    /// 只移除 `locks` 中列出的锁 (通常是已确认过期的锁)
+    ///
+    /// 锁在刷新时会以新的 ID 重新写入，因此重新列出后仍然存在的这些 ID 都未被刷新过，
+    /// 检查之后才出现的锁不会被移除。restic 不支持按 ID 移除锁，本地仓库直接删除 `locks/<ID>` 文件；
+    /// 远程仓库改为执行不带 `--remove-all` 的 `restic unlock`，只移除 restic 自己判断为过期的锁。
+    pub fn remove_locks(&self, locks: &[LockInfo]) -> Result<(), Error> {
+        if utils::is_remote_repo(&self.repo) {
+            return self.unlock(false);
+        }
+        let current = self.run_once(&["--no-lock", "list", "locks"])?;
+        let locks_dir = Path::new(&self.repo).join("locks");
+        for id in current.lines().map(str::trim).filter(|id| locks.iter().any(|lock| lock.id == *id)) {
+            match std::fs::remove_file(locks_dir.join(id)) {
+                Ok(()) => {}
+                // 已被持有者自己释放
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    return Err(Error::PermissionDenied(format!("删除锁文件 {} 失败: {}", id, e)));
+                }
+                Err(e) => return Err(format!("删除锁文件 {} 失败: {}", id, e).into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// 执行 `restic check`，`read_data_subset` 不为空时同时校验对应比例的数据包内容
+    pub fn check(&self, read_data_subset: Option<&str>) -> Result<String, Error> {
+        let mut args = vec!["check"];
+        if let Some(subset) = read_data_subset {
+            args.push("--read-data-subset");
+            args.push(subset);
+        }
+        self.run(&args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(line: &str) -> BackupMessage {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[test]
+    fn decodes_status_messages() {
+        let line = r#"{"message_type":"status","seconds_elapsed":3,"seconds_remaining":7,"percent_done":0.25,"total_files":8,"files_done":2,"total_bytes":4096,"bytes_done":1024,"current_files":["/data/a.txt"]}"#;
+        let BackupMessage::Status(status) = decode(line) else { panic!("不是 status 消息") };
+        assert_eq!(status.percent_done, 0.25);
+        assert_eq!(status.seconds_remaining, Some(7));
+        assert_eq!((status.files_done, status.total_files), (2, 8));
+        assert_eq!(status.current_files, ["/data/a.txt"]);
+
+        // 统计完成前 restic 不输出总量与剩余时间
+        let BackupMessage::Status(status) = decode(r#"{"message_type":"status","percent_done":0,"files_done":1}"#) else { panic!("不是 status 消息") };
+        assert_eq!(status.total_bytes, 0);
+        assert_eq!(status.seconds_remaining, None);
+    }
+
+    #[test]
+    fn decodes_summary_messages() {
+        let line = r#"{"message_type":"summary","files_new":3,"files_changed":1,"files_unmodified":6,"data_added":1234,"data_added_packed":600,"total_files_processed":10,"total_bytes_processed":1000,"total_duration":1.5,"snapshot_id":"0123456789abcdef"}"#;
+        let BackupMessage::Summary(summary) = decode(line) else { panic!("不是 summary 消息") };
+        assert_eq!(summary.files_new, 3);
+        assert_eq!(summary.data_added_packed, 600);
+        assert_eq!(summary.short_id().as_deref(), Some("01234567"));
+        assert_eq!(summary.read_errors, 0);
+
+        // 旧版本没有 data_added_packed 时不计算压缩比
+        let BackupMessage::Summary(summary) = decode(r#"{"message_type":"summary","data_added":1234,"snapshot_id":"abc"}"#) else { panic!("不是 summary 消息") };
+        assert_eq!(summary.compression_ratio(), None);
+    }
+
+    #[test]
+    fn decodes_error_messages() {
+        let line = r#"{"message_type":"error","error":{"message":"open /data/secret: permission denied"},"during":"archival","item":"/data/secret"}"#;
+        let BackupMessage::Error(e) = decode(line) else { panic!("不是 error 消息") };
+        assert_eq!(e.item, "/data/secret");
+        assert_eq!(e.error.message, "open /data/secret: permission denied");
+    }
+
+    #[test]
+    fn ignores_unknown_message_types_and_rejects_plain_text() {
+        assert!(matches!(decode(r#"{"message_type":"verbose_status","action":"new","item":"/x"}"#), BackupMessage::Other));
+        assert!(serde_json::from_str::<BackupMessage>("repository 1234 opened (version 2, compression level auto)").is_err());
+        assert!(serde_json::from_str::<BackupMessage>(r#"{"percent_done":0.5}"#).is_err());
+    }
+
+    #[test]
+    fn forwards_stderr_errors_as_events() {
+        let (tx, rx) = mpsc::channel();
+        for item in ["/a", "/b"] {
+            tx.send(BackupError { error: BackupErrorDetail { message: "denied".to_string() }, item: item.to_string() }).unwrap();
+        }
+        let mut items = Vec::new();
+        let count = forward_errors(&rx, &mut |event| {
+            if let BackupEvent::Error { item, message } = event {
+                assert_eq!(message, "denied");
+                items.push(item);
+            }
+        });
+        assert_eq!(count, 2);
+        assert_eq!(items, ["/a", "/b"]);
+        assert_eq!(forward_errors(&rx, &mut |_| panic!("没有新的错误")), 0);
+    }
+}
@@ -1,78 +1,602 @@
+use crate::archive::ArchiveFormat;
+use crate::check;
+use crate::client::{Compression, InitOptions, SourceRepo};
+use crate::error::{Error, FieldError};
+use crate::lock::UnlockPolicy;
+use crate::schedule::{self, Schedule};
+use crate::utils;
+use crate::version;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::Path;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 仓库密码的来源
+///
+/// 除 `Plain` 外，密码本身都不会出现在配置文件中；任何来源的密码都不会出现在 restic 的命令行参数里。
+#[derive(Debug, Clone)]
+pub enum PasswordSource {
+    /// 明文密码，通过标准输入传给 restic
+    Plain(String),
+    /// 密码文件，对应 restic 的 `--password-file`
+    File(String),
+    /// 输出密码的命令，对应 restic 的 `--password-command`
+    Command(String),
+    /// 保存密码的环境变量名，运行时读取后通过标准输入传给 restic
+    Env(String),
+}
+
+/// 配置文件中可选的四种密码字段，同一层级 (局部或全局) 最多只能设置一个
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct PasswordFields {
+    pub passwd: Option<String>,
+    pub passwd_file: Option<String>,
+    pub passwd_command: Option<String>,
+    pub passwd_env: Option<String>,
+}
+
+impl PasswordFields {
+    fn sources(&self) -> Vec<PasswordSource> {
+        let mut sources = Vec::new();
+        if let Some(p) = &self.passwd {
+            sources.push(PasswordSource::Plain(p.clone()));
+        }
+        if let Some(f) = &self.passwd_file {
+            sources.push(PasswordSource::File(f.clone()));
+        }
+        if let Some(c) = &self.passwd_command {
+            sources.push(PasswordSource::Command(c.clone()));
+        }
+        if let Some(v) = &self.passwd_env {
+            sources.push(PasswordSource::Env(v.clone()));
+        }
+        sources
+    }
+
+    /// 确定任务的密码来源: 局部设置了任意一种来源时忽略全局配置，否则使用全局配置
+    fn resolve(&self, global: &PasswordFields, key_name: &str) -> Result<PasswordSource, FieldError> {
+        let local_sources = self.sources();
+        let (sources, scope) = if local_sources.is_empty() {
+            (global.sources(), "全局")
+        } else {
+            (local_sources, "局部")
+        };
+
+        if sources.len() > 1 {
+            return Err(FieldError::new(key_name, "passwd", format!("{}配置中 `passwd` / `passwd_file` / `passwd_command` / `passwd_env` 只能设置一个。", scope)));
+        }
+        let Some(source) = sources.into_iter().next() else {
+            return Err(FieldError::new(key_name, "passwd", "未设置仓库密码 (`passwd` / `passwd_file` / `passwd_command` / `passwd_env`，全局或局部必须设置一个)。"));
+        };
+
+        match &source {
+            PasswordSource::Plain(p) if p.is_empty() => {
+                Err(FieldError::new(key_name, "passwd", "`passwd` 字段不能为空。"))
+            }
+            PasswordSource::File(f) if !Path::new(f).is_file() => {
+                Err(FieldError::new(key_name, "passwd_file", format!("密码文件 '{}' 不存在。", f)))
+            }
+            PasswordSource::Command(c) if c.trim().is_empty() => {
+                Err(FieldError::new(key_name, "passwd_command", "`passwd_command` 字段不能为空。"))
+            }
+            PasswordSource::Env(v) if env::var(v).map_or(true, |val| val.is_empty()) => {
+                Err(FieldError::new(key_name, "passwd_env", format!("环境变量 '{}' 未设置或为空。", v)))
+            }
+            _ => Ok(source),
+        }
+    }
+}
+
+/// 快照保留策略，对应 `restic forget` 的 `--keep-*` 与 `--prune` 参数
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<u32>,
+    pub keep_daily: Option<u32>,
+    pub keep_weekly: Option<u32>,
+    pub keep_monthly: Option<u32>,
+    pub keep_yearly: Option<u32>,
+    pub keep_tag: Option<String>,
+    pub prune: Option<bool>,
+}
+
+impl RetentionPolicy {
+    /// 逐项合并: 局部配置优先，其次全局配置
+    fn or(&self, global: &RetentionPolicy) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: self.keep_last.or(global.keep_last),
+            keep_daily: self.keep_daily.or(global.keep_daily),
+            keep_weekly: self.keep_weekly.or(global.keep_weekly),
+            keep_monthly: self.keep_monthly.or(global.keep_monthly),
+            keep_yearly: self.keep_yearly.or(global.keep_yearly),
+            keep_tag: self.keep_tag.clone().or(global.keep_tag.clone()),
+            prune: self.prune.or(global.prune),
+        }
+    }
+
+    /// 是否设置了任何 `keep_*` 规则
+    pub fn has_rules(&self) -> bool {
+        self.keep_last.is_some()
+            || self.keep_daily.is_some()
+            || self.keep_weekly.is_some()
+            || self.keep_monthly.is_some()
+            || self.keep_yearly.is_some()
+            || self.keep_tag.is_some()
+    }
+
+    /// 生成 `restic forget` 的参数 (不含子命令本身)
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let counts = [
+            ("--keep-last", self.keep_last),
+            ("--keep-daily", self.keep_daily),
+            ("--keep-weekly", self.keep_weekly),
+            ("--keep-monthly", self.keep_monthly),
+            ("--keep-yearly", self.keep_yearly),
+        ];
+        for (flag, value) in counts {
+            if let Some(n) = value {
+                args.push(flag.to_string());
+                args.push(n.to_string());
+            }
+        }
+        if let Some(tag) = &self.keep_tag {
+            args.push("--keep-tag".to_string());
+            args.push(tag.clone());
+        }
+        if self.prune.unwrap_or(false) {
+            args.push("--prune".to_string());
+        }
+        args
+    }
+}
+
+/// 备份时的排除规则，对应 `restic backup` 的 `--exclude*` 参数
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ExcludeOptions {
+    pub exclude: Option<Vec<String>>,
+    pub iexclude: Option<Vec<String>>,
+    pub exclude_file: Option<Vec<String>>,
+    pub exclude_if_present: Option<Vec<String>>,
+    pub exclude_caches: Option<bool>,
+    pub exclude_larger_than: Option<String>,
+}
+
+impl ExcludeOptions {
+    /// 逐项合并: 局部配置优先，其次全局配置
+    fn or(&self, global: &ExcludeOptions) -> ExcludeOptions {
+        ExcludeOptions {
+            exclude: self.exclude.clone().or(global.exclude.clone()),
+            iexclude: self.iexclude.clone().or(global.iexclude.clone()),
+            exclude_file: self.exclude_file.clone().or(global.exclude_file.clone()),
+            exclude_if_present: self.exclude_if_present.clone().or(global.exclude_if_present.clone()),
+            exclude_caches: self.exclude_caches.or(global.exclude_caches),
+            exclude_larger_than: self.exclude_larger_than.clone().or(global.exclude_larger_than.clone()),
+        }
+    }
+
+    /// 生成 `restic backup` 的排除参数
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let repeated = [
+            ("--exclude", &self.exclude),
+            ("--iexclude", &self.iexclude),
+            ("--exclude-file", &self.exclude_file),
+            ("--exclude-if-present", &self.exclude_if_present),
+        ];
+        for (flag, values) in repeated {
+            for value in values.iter().flatten() {
+                args.push(flag.to_string());
+                args.push(value.clone());
+            }
+        }
+        if self.exclude_caches.unwrap_or(false) {
+            args.push("--exclude-caches".to_string());
+        }
+        if let Some(size) = &self.exclude_larger_than {
+            args.push("--exclude-larger-than".to_string());
+            args.push(size.clone());
+        }
+        args
+    }
+
+    /// 验证排除规则，返回错误列表 (为空表示通过)
+    fn validate(&self, key_name: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for file in self.exclude_file.iter().flatten() {
+            if !Path::new(file).is_file() {
+                errors.push(FieldError::new(key_name, "exclude_file", format!("排除规则文件 '{}' 不存在。", file)));
+            }
+        }
+        if let Some(size) = &self.exclude_larger_than {
+            if utils::parse_size(size).is_none() {
+                errors.push(FieldError::new(key_name, "exclude_larger_than", format!("`exclude_larger_than` 格式无效: '{}' (示例: \"500M\", \"2G\")。", size)));
+            }
+        }
+        errors
+    }
+}
+
+/// 备份前后执行的钩子命令
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct HookOptions {
+    /// 备份前执行，例如导出数据库、停止服务
+    pub pre_command: Option<String>,
+    /// 备份 (及清理、校验) 结束后执行，无论成功与否，例如重新启动服务
+    pub post_command: Option<String>,
+    /// 任务最终成功时执行
+    pub on_success: Option<String>,
+    /// 任务最终失败时执行
+    pub on_failure: Option<String>,
+    /// 单个钩子命令的超时时间 (秒)，0 表示不限制，默认 600
+    pub hook_timeout: Option<u64>,
+    /// `pre_command` 失败时是否放弃本次备份，默认 true
+    pub abort_on_pre_failure: Option<bool>,
+}
+
+impl HookOptions {
+    /// 逐项合并: 局部配置优先，其次全局配置
+    fn or(&self, global: &HookOptions) -> HookOptions {
+        HookOptions {
+            pre_command: self.pre_command.clone().or(global.pre_command.clone()),
+            post_command: self.post_command.clone().or(global.post_command.clone()),
+            on_success: self.on_success.clone().or(global.on_success.clone()),
+            on_failure: self.on_failure.clone().or(global.on_failure.clone()),
+            hook_timeout: self.hook_timeout.or(global.hook_timeout),
+            abort_on_pre_failure: self.abort_on_pre_failure.or(global.abort_on_pre_failure),
+        }
+    }
+
+    /// 验证钩子命令，返回错误列表 (为空表示通过)
+    fn validate(&self, key_name: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let commands = [
+            ("pre_command", &self.pre_command),
+            ("post_command", &self.post_command),
+            ("on_success", &self.on_success),
+            ("on_failure", &self.on_failure),
+        ];
+        for (field, command) in commands {
+            if command.as_ref().is_some_and(|c| c.trim().is_empty()) {
+                errors.push(FieldError::new(key_name, field, format!("`{}` 不能为空字符串。", field)));
+            }
+        }
+        errors
+    }
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TomlConfig {
     pub name: Option<String>,
+    /// 仓库位置 (本地路径或 "rest:http://..."、"sftp:..." 等远程 URL)，设置后忽略 restic_home_path
+    #[serde(alias = "repository")]
+    pub repo: Option<String>,
     pub path: Option<Vec<String>>,
+    /// "path" (默认) 或 "archive"
+    pub source: Option<String>,
     pub tag: Option<String>,
-    pub passwd: Option<String>,
+    #[serde(flatten)]
+    pub password: PasswordFields,
     pub restic_home_path: Option<String>,
     pub merge: Option<i64>,
-    pub merge_name: Option<String>,
     #[serde(alias = "pack_site")] // 兼容用户可能的拼写错误
     pub pack_size: Option<u64>,
+    /// 压缩模式: "auto"、"max" 或 "off"
+    pub compression: Option<String>,
+    #[serde(flatten)]
+    pub retention: RetentionPolicy,
+    #[serde(flatten)]
+    pub exclude: ExcludeOptions,
+    #[serde(flatten)]
+    pub hooks: HookOptions,
+    pub check_after_backup: Option<bool>,
+    pub read_data_subset: Option<String>,
+    pub diff_after_backup: Option<bool>,
+    /// 仓库锁超过该时长未刷新时自动移除，例如 "2h"
+    pub auto_unlock_stale_after: Option<String>,
+    pub priority: Option<i64>,
+    pub depends_on: Option<Vec<String>>,
+    /// 守护模式下的运行计划，例如 "every 6h" 或 "0 3 * * *"
+    pub schedule: Option<String>,
+    /// 该任务使用的 restic 可执行文件
+    pub restic_binary: Option<String>,
+    /// 传给 restic 进程的额外环境变量，与全局配置合并 (同名时局部优先)
+    pub env: Option<IndexMap<String, String>>,
+    /// 新建仓库时的格式版本，可写数字 (1、2) 或 "latest" / "stable"
+    pub repo_version: Option<RepoVersionField>,
+    /// 新建仓库时从该任务 (配置键名) 的仓库复制分块参数
+    pub copy_chunker_params_from: Option<String>,
+    /// 新建仓库时从该仓库 (本地路径或远程 URL) 复制分块参数
+    pub from_repo: Option<String>,
+    /// `from_repo` 的密码来源，都未设置时使用本任务的密码
+    pub from_passwd: Option<String>,
+    pub from_passwd_file: Option<String>,
+    pub from_passwd_command: Option<String>,
+    pub from_passwd_env: Option<String>,
+}
+
+/// `repo_version` 字段，可以写成数字或字符串
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RepoVersionField {
+    Number(i64),
+    Text(String),
+}
+
+impl RepoVersionField {
+    /// 转换为 restic `--repository-version` 接受的值
+    fn resolve(&self) -> Result<String, String> {
+        let value = match self {
+            RepoVersionField::Number(n) => n.to_string(),
+            RepoVersionField::Text(s) => s.trim().to_lowercase(),
+        };
+        match value.as_str() {
+            "1" | "2" | "latest" | "stable" => Ok(value),
+            _ => Err(format!("`repo_version` 只能是 1、2、\"latest\" 或 \"stable\"，当前值: '{}'。", value)),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct GlobalConfig {
     pub merge: Option<i64>,
-    pub passwd: Option<String>,
+    #[serde(flatten)]
+    pub password: PasswordFields,
     pub restic_home_path: Option<String>,
     pub tag: Option<String>,
     #[serde(alias = "pack_site")] // 兼容用户可能的拼写错误
     pub pack_size: Option<u64>,
+    pub compression: Option<String>,
+    #[serde(flatten)]
+    pub retention: RetentionPolicy,
+    #[serde(flatten)]
+    pub exclude: ExcludeOptions,
+    #[serde(flatten)]
+    pub hooks: HookOptions,
+    pub check_after_backup: Option<bool>,
+    pub read_data_subset: Option<String>,
+    pub diff_after_backup: Option<bool>,
+    pub auto_unlock_stale_after: Option<String>,
+    pub restic_binary: Option<String>,
+    pub env: Option<IndexMap<String, String>>,
+    pub repo_version: Option<RepoVersionField>,
+    /// 最多同时执行的任务数 (仅全局配置)
+    pub max_parallel: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
 struct TomlConfigFile {
     #[serde(default)]
     global_config: GlobalConfig,
-    config: HashMap<String, TomlConfig>,
+    config: IndexMap<String, TomlConfig>,
+}
+
+/// 备份源的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupSource {
+    /// `path` 是要备份的文件或目录
+    Paths,
+    /// `path` 是 zip / tar 归档 (或存放归档的目录)，逐个解压后导入，每个归档生成一个快照
+    Archives,
 }
 
 #[derive(Debug, Clone)]
 pub struct FinalConfig {
     pub key_name: String,
     pub name: String,
+    /// 直接指定的仓库位置 (本地路径或远程 URL)，`None` 时为 `restic_home_path/name`
+    pub repo: Option<String>,
     pub path: Vec<String>,
+    pub source: BackupSource,
     pub tag: String,
-    pub passwd: String,
+    pub passwd: PasswordSource,
     pub restic_home_path: String,
     pub merge: i64,
-    pub merge_name: String,
     pub pack_size: u64,
+    /// 压缩模式，`None` 时使用 restic 默认值
+    pub compression: Option<Compression>,
+    pub retention: RetentionPolicy,
+    pub exclude: ExcludeOptions,
+    pub hooks: HookOptions,
+    /// 备份成功后是否执行 `restic check`
+    pub check_after_backup: bool,
+    /// 校验时读取的数据比例，对应 `--read-data-subset`
+    pub read_data_subset: Option<String>,
+    /// 备份成功后是否打印与父快照之间的简短差异
+    pub diff_after_backup: bool,
+    /// 仓库锁超过该时长未刷新时视为过期，自动移除后重试；`None` 表示从不自动移除
+    pub auto_unlock_stale_after: Option<Duration>,
+    /// 执行优先级，数值越大越先执行 (默认 0)
+    pub priority: i64,
+    /// 必须先成功完成的任务 (配置键名)
+    pub depends_on: Vec<String>,
+    /// 守护模式下的运行计划，未设置时守护模式不会运行该任务
+    pub schedule: Option<Schedule>,
+    /// 该任务使用的 restic 可执行文件，`None` 时使用自动查找到的 restic
+    pub restic_binary: Option<String>,
+    /// 传给 restic 进程的额外环境变量
+    pub env: Vec<(String, String)>,
+    /// 仓库不存在、需要新建时的选项
+    pub init: InitOptions,
+}
+
+impl FinalConfig {
+    /// 该任务使用的 restic 可执行文件，未设置 `restic_binary` 时为 `default`
+    ///
+    /// `default` 为空表示没有找到全局的 restic，此时未设置 `restic_binary` 的任务返回 `Error::ResticMissing`。
+    pub fn restic_exe_path<'a>(&'a self, default: &'a str) -> Result<&'a str, Error> {
+        match self.restic_binary.as_deref() {
+            Some(binary) => Ok(binary),
+            None if default.is_empty() => Err(Error::ResticMissing),
+            None => Ok(default),
+        }
+    }
+
+    /// 任务对应的仓库路径: `repo`，未设置时为 `restic_home_path/name`
+    ///
+    /// 远程 URL 也以 `PathBuf` 原样保存，只用于传给 restic 的 `-r` 参数与显示。
+    pub fn repo_path(&self) -> PathBuf {
+        match &self.repo {
+            Some(repo) => PathBuf::from(repo),
+            None => Path::new(&self.restic_home_path).join(&self.name),
+        }
+    }
+
+    /// 仓库被锁定时的处理方式
+    pub fn unlock_policy(&self) -> UnlockPolicy {
+        self.auto_unlock_stale_after.map_or(UnlockPolicy::Never, UnlockPolicy::StaleAfter)
+    }
+}
+
+/// 一个备份配置文件解析后的全部内容
+#[derive(Debug, Clone)]
+pub struct BackupPlan {
+    /// 按执行顺序排列的备份任务
+    pub jobs: Vec<FinalConfig>,
+    /// 最多同时执行的任务数 (默认 1，即依次执行)
+    pub max_parallel: usize,
 }
 
 /// 解析 TOML 配置文件并验证
-pub fn parse_toml(file_path: &str) -> Result<Vec<FinalConfig>, String> {
+pub fn parse_toml(file_path: &str) -> Result<BackupPlan, Error> {
     let toml_content = std::fs::read_to_string(file_path)
-        .map_err(|e| format!("读取 TOML 文件 '{}' 失败: {}", file_path, e))?;
+        .map_err(|e| Error::config(file_path, "", "", format!("读取 TOML 文件失败: {}", e)))?;
     
     let config_file: TomlConfigFile = toml::from_str(&toml_content)
-        .map_err(|e| format!("TOML 文件解析失败: {}", e))?;
+        .map_err(|e| Error::config(file_path, "", "", format!("TOML 文件解析失败: {}", e)))?;
 
     let mut final_configs = Vec::new();
-    let mut error_messages = String::new();
+    let mut errors = Vec::new();
+
+    let max_parallel = config_file.global_config.max_parallel.unwrap_or(1);
+    if max_parallel == 0 {
+        errors.push(FieldError::new("global_config", "max_parallel", "`max_parallel` 必须大于 0。"));
+    }
+    let job_keys: Vec<String> = config_file.config.keys().cloned().collect();
+    // (任务下标, 来源任务键名)，用于 copy_chunker_params_from
+    let mut chunker_sources: Vec<(usize, String)> = Vec::new();
 
     for (key_name, cfg) in config_file.config {
+        let passwd = match cfg.password.resolve(&config_file.global_config.password, &key_name) {
+            Ok(source) => source,
+            Err(e) => {
+                errors.push(e);
+                PasswordSource::Plain(String::new())
+            }
+        };
         let mut final_cfg = FinalConfig {
             key_name: key_name.clone(),
             name: cfg.name.unwrap_or_else(|| key_name.clone()),
+            repo: cfg.repo.map(|repo| repo.trim().to_string()),
             path: cfg.path.unwrap_or_default(),
+            source: BackupSource::Paths,
             tag: cfg.tag.or(config_file.global_config.tag.clone()).unwrap_or_default(),
-            passwd: cfg.passwd.or(config_file.global_config.passwd.clone()).unwrap_or_default(),
+            passwd,
             restic_home_path: cfg.restic_home_path.or(config_file.global_config.restic_home_path.clone()).unwrap_or_default(),
             merge: cfg.merge.unwrap_or_else(|| config_file.global_config.merge.unwrap_or(0)),
-            merge_name: cfg.merge_name.unwrap_or_else(|| "merged_backup".to_string()),
             // 优先使用局部配置，其次全局配置，默认 128 (优化大文件性能)
             pack_size: cfg.pack_size.or(config_file.global_config.pack_size).unwrap_or(128),
+            compression: None,
+            retention: cfg.retention.or(&config_file.global_config.retention),
+            exclude: cfg.exclude.or(&config_file.global_config.exclude),
+            hooks: cfg.hooks.or(&config_file.global_config.hooks),
+            check_after_backup: cfg.check_after_backup.or(config_file.global_config.check_after_backup).unwrap_or(false),
+            read_data_subset: cfg.read_data_subset.or(config_file.global_config.read_data_subset.clone()),
+            diff_after_backup: cfg.diff_after_backup.or(config_file.global_config.diff_after_backup).unwrap_or(false),
+            auto_unlock_stale_after: None,
+            priority: cfg.priority.unwrap_or(0),
+            depends_on: cfg.depends_on.unwrap_or_default(),
+            schedule: None,
+            restic_binary: cfg.restic_binary.or(config_file.global_config.restic_binary.clone()),
+            env: merge_env(config_file.global_config.env.as_ref(), cfg.env.as_ref()),
+            init: InitOptions::default(),
         };
+        errors.extend(final_cfg.exclude.validate(&key_name));
+        errors.extend(validate_restic_env(&key_name, final_cfg.restic_binary.as_deref(), &final_cfg.env));
+        errors.extend(final_cfg.hooks.validate(&key_name));
 
         // 验证 pack_size
         if final_cfg.pack_size < 16 || final_cfg.pack_size > 128 {
-            error_messages.push_str(&format!("[{}]: `pack_size` 必须在 16 到 128 (MiB) 之间，当前值: {}。\n", key_name, final_cfg.pack_size));
+            errors.push(FieldError::new(&key_name, "pack_size", format!("`pack_size` 必须在 16 到 128 (MiB) 之间，当前值: {}。", final_cfg.pack_size)));
+        }
+
+        if let Some(value) = cfg.compression.as_ref().or(config_file.global_config.compression.as_ref()) {
+            match Compression::parse(value) {
+                Ok(compression) => final_cfg.compression = Some(compression),
+                Err(e) => errors.push(FieldError::new(&key_name, "compression", format!("`compression` 无效: {}。", e))),
+            }
+        }
+
+        if let Some(field) = cfg.repo_version.as_ref().or(config_file.global_config.repo_version.as_ref()) {
+            match field.resolve() {
+                Ok(version) => final_cfg.init.repo_version = Some(version),
+                Err(e) => errors.push(FieldError::new(&key_name, "repo_version", e)),
+            }
+        }
+        if final_cfg.init.repo_version.as_deref() == Some("1") && final_cfg.compression == Some(Compression::Max) {
+            errors.push(FieldError::new(&key_name, "compression", "`repo_version = 1` 的仓库不支持压缩，不能设置 `compression = \"max\"`。"));
+        }
+
+        // 验证分块参数来源: 来源任务的仓库与密码在所有任务解析完成后再填入，并自动依赖来源任务
+        let from_password = PasswordFields {
+            passwd: cfg.from_passwd.clone(),
+            passwd_file: cfg.from_passwd_file.clone(),
+            passwd_command: cfg.from_passwd_command.clone(),
+            passwd_env: cfg.from_passwd_env.clone(),
+        }
+        .sources();
+        if from_password.len() > 1 {
+            errors.push(FieldError::new(&key_name, "from_passwd", "`from_passwd` / `from_passwd_file` / `from_passwd_command` / `from_passwd_env` 只能设置一个。"));
+        } else if !from_password.is_empty() && cfg.from_repo.is_none() {
+            errors.push(FieldError::new(&key_name, "from_passwd", "`from_passwd*` 只能与 `from_repo` 一起使用。"));
+        }
+        match (&cfg.copy_chunker_params_from, &cfg.from_repo) {
+            (Some(_), Some(_)) => errors.push(FieldError::new(&key_name, "from_repo", "`copy_chunker_params_from` 与 `from_repo` 只能设置一个。")),
+            (Some(source), None) if source == &key_name => {
+                errors.push(FieldError::new(&key_name, "copy_chunker_params_from", "`copy_chunker_params_from` 不能指向自身。"));
+            }
+            (Some(source), None) if !job_keys.contains(source) => {
+                errors.push(FieldError::new(&key_name, "copy_chunker_params_from", format!("`copy_chunker_params_from` 中的任务 '{}' 不存在。", source)));
+            }
+            (Some(source), None) => {
+                if !final_cfg.depends_on.contains(source) {
+                    final_cfg.depends_on.push(source.clone());
+                }
+                chunker_sources.push((final_configs.len(), source.clone()));
+            }
+            (None, Some(repo)) if repo.trim().is_empty() => errors.push(FieldError::new(&key_name, "from_repo", "`from_repo` 不能为空字符串。")),
+            (None, Some(repo)) => {
+                let password = from_password.into_iter().next().unwrap_or_else(|| final_cfg.passwd.clone());
+                final_cfg.init.copy_chunker_params_from = Some(SourceRepo { repo: repo.trim().to_string(), password });
+            }
+            (None, None) => {}
+        }
+
+        // 验证保留策略: 只设置 prune 而没有任何 keep_* 规则时 restic 不会删除任何快照
+        if final_cfg.retention.prune.unwrap_or(false) && !final_cfg.retention.has_rules() {
+            errors.push(FieldError::new(&key_name, "prune", "设置了 `prune = true` 但没有任何 `keep_*` 保留规则。"));
+        }
+
+        // 验证 read_data_subset
+        if let Some(subset) = &final_cfg.read_data_subset {
+            if !check::is_valid_data_subset(subset) {
+                errors.push(FieldError::new(&key_name, "read_data_subset", format!("`read_data_subset` 格式无效: '{}' (示例: \"5%\", \"1/10\", \"2G\")。", subset)));
+            }
+        }
+
+        if let Some(value) = cfg.auto_unlock_stale_after.as_ref().or(config_file.global_config.auto_unlock_stale_after.as_ref()) {
+            match schedule::parse_duration(value) {
+                // restic 每 5 分钟刷新一次正在使用的锁，更短的时长可能误删活跃进程的锁
+                Some(limit) if limit.as_secs() >= 600 => final_cfg.auto_unlock_stale_after = Some(limit),
+                Some(_) => errors.push(FieldError::new(&key_name, "auto_unlock_stale_after", format!("`auto_unlock_stale_after` 不能小于 10 分钟，当前值: '{}'。", value))),
+                None => errors.push(FieldError::new(&key_name, "auto_unlock_stale_after", format!("`auto_unlock_stale_after` 格式无效: '{}' (示例: \"30m\", \"2h\", \"1d\")。", value))),
+            }
+        }
+
+        match cfg.source.as_deref() {
+            None | Some("path") => {}
+            Some("archive") => final_cfg.source = BackupSource::Archives,
+            Some(other) => errors.push(FieldError::new(&key_name, "source", format!("`source` 只能是 \"path\" 或 \"archive\"，当前值: '{}'。", other))),
         }
 
         // 智能判断 merge 默认值
@@ -82,60 +606,155 @@ pub fn parse_toml(file_path: &str) -> Result<Vec<FinalConfig>, String> {
 
         // 验证必填字段
         if final_cfg.path.is_empty() {
-            error_messages.push_str(&format!("[{}]: `path` 字段不能为空。\n", key_name));
-        }
-        if final_cfg.passwd.is_empty() {
-            error_messages.push_str(&format!("[{}]: `passwd` 字段不能为空 (全局或局部必须设置一个)。\n", key_name));
+            errors.push(FieldError::new(&key_name, "path", "`path` 字段不能为空。"));
         }
-        if final_cfg.restic_home_path.is_empty() {
-            error_messages.push_str(&format!("[{}]: `restic_home_path` 字段不能为空 (全局或局部必须设置一个)。\n", key_name));
+        match &final_cfg.repo {
+            Some(repo) if repo.is_empty() => errors.push(FieldError::new(&key_name, "repo", "`repo` 字段不能为空字符串。")),
+            Some(_) => {}
+            None if final_cfg.restic_home_path.is_empty() => {
+                errors.push(FieldError::new(&key_name, "restic_home_path", "`restic_home_path` 字段不能为空 (全局或局部必须设置一个，或直接设置 `repo`)。"));
+            }
+            None => {}
         }
         if final_cfg.merge != 0 && final_cfg.merge != 1 {
-            error_messages.push_str(&format!("[{}]: `merge` 字段必须为 0 或 1。\n", key_name));
+            errors.push(FieldError::new(&key_name, "merge", "`merge` 字段必须为 0 或 1。"));
+        }
+        if final_cfg.source == BackupSource::Archives && cfg.merge == Some(1) {
+            errors.push(FieldError::new(&key_name, "merge", "`source = \"archive\"` 时每个归档单独生成快照，不能设置 `merge = 1`。"));
+        }
+
+        // 验证运行计划
+        if let Some(schedule) = &cfg.schedule {
+            match Schedule::parse(schedule) {
+                Ok(parsed) => final_cfg.schedule = Some(parsed),
+                Err(e) => errors.push(FieldError::new(&key_name, "schedule", e)),
+            }
+        }
+
+        // 验证依赖项
+        for dep in &final_cfg.depends_on {
+            if dep == &key_name {
+                errors.push(FieldError::new(&key_name, "depends_on", "`depends_on` 不能依赖自身。"));
+            } else if !job_keys.contains(dep) {
+                errors.push(FieldError::new(&key_name, "depends_on", format!("`depends_on` 中的任务 '{}' 不存在。", dep)));
+            }
         }
 
         // 验证路径存在性
         for p in &final_cfg.path {
             if !Path::new(p).exists() {
-                error_messages.push_str(&format!("[{}]: 备份源路径 '{}' 不存在。\n", key_name, p));
+                errors.push(FieldError::new(&key_name, "path", format!("备份源路径 '{}' 不存在。", p)));
             }
         }
         
         final_configs.push(final_cfg);
     }
 
-    if !error_messages.is_empty() {
-        return Err(format!("配置文件验证失败:\n{}", error_messages));
+    if !errors.is_empty() {
+        return Err(Error::ConfigInvalid { file: file_path.to_string(), errors });
     }
 
     if final_configs.is_empty() {
-        return Err("配置文件中未找到任何有效的 [config] 配置项。".to_string());
+        return Err(Error::config(file_path, "config", "", "配置文件中未找到任何有效的 [config] 配置项。"));
+    }
+
+    for (i, source_key) in chunker_sources {
+        let source = final_configs.iter().find(|cfg| cfg.key_name == source_key).expect("已验证来源任务存在");
+        let source_repo = SourceRepo { repo: source.repo_path().to_string_lossy().into_owned(), password: source.passwd.clone() };
+        final_configs[i].init.copy_chunker_params_from = Some(source_repo);
     }
     
-    Ok(final_configs)
+    let jobs = order_by_dependencies(final_configs)
+        .map_err(|e| Error::ConfigInvalid { file: file_path.to_string(), errors: vec![e] })?;
+    Ok(BackupPlan { jobs, max_parallel })
+}
+
+/// 按依赖关系与优先级排列任务
+///
+/// 每一步从依赖已全部排好的任务中选出 `priority` 最大的一个，优先级相同时保持配置文件中的声明顺序。
+fn order_by_dependencies(mut pending: Vec<FinalConfig>) -> Result<Vec<FinalConfig>, FieldError> {
+    let mut ordered: Vec<FinalConfig> = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, cfg)| cfg.depends_on.iter().all(|dep| ordered.iter().any(|done| &done.key_name == dep)))
+            // max_by_key 在相等时取最后一个，这里反转下标以保留声明顺序
+            .max_by_key(|(i, cfg)| (cfg.priority, std::cmp::Reverse(*i)))
+            .map(|(i, _)| i);
+
+        match ready {
+            Some(i) => ordered.push(pending.remove(i)),
+            None => {
+                let names: Vec<&str> = pending.iter().map(|cfg| cfg.key_name.as_str()).collect();
+                return Err(FieldError::new("config", "depends_on", format!("以下任务的 `depends_on` 存在循环依赖: {}", names.join(", "))));
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// 合并全局与局部的 `env` 表，同名变量局部优先，保持声明顺序
+fn merge_env(global: Option<&IndexMap<String, String>>, local: Option<&IndexMap<String, String>>) -> Vec<(String, String)> {
+    let mut merged = global.cloned().unwrap_or_default();
+    merged.extend(local.cloned().unwrap_or_default());
+    merged.into_iter().collect()
+}
+
+/// 验证 `restic_binary` 可以执行且版本受支持，以及 `env` 中的变量名有效
+fn validate_restic_env(section: &str, restic_binary: Option<&str>, env: &[(String, String)]) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(binary) = restic_binary {
+        match version::detect(binary) {
+            Some(v) => {
+                if let Err(e) = v.ensure_supported() {
+                    errors.push(FieldError::new(section, "restic_binary", format!("`restic_binary` '{}': {}", binary, e)));
+                }
+            }
+            None => errors.push(FieldError::new(section, "restic_binary", format!("`restic_binary` '{}' 无法执行或无法识别其版本。", binary))),
+        }
+    }
+    for (key, _) in env {
+        if key.is_empty() || key.contains('=') {
+            errors.push(FieldError::new(section, "env", format!("`env` 中的变量名 '{}' 无效。", key)));
+        }
+    }
+    errors
 }
 
 // ----- STRUCTS FOR BATCH RESTORE -----
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct RestoreJob {
+    /// 仓库位置，本地路径或远程 URL
+    #[serde(alias = "repository")]
     pub repo: Option<String>,
     pub target: Option<String>,
-    pub passwd: Option<String>,
+    #[serde(flatten)]
+    pub password: PasswordFields,
     pub snapshots: Option<String>,
     pub restore_path: Option<String>, // 新增: 指定快照中要恢复的子路径
+    /// 设置后不写出散落的文件，而是在 `target` 目录中生成归档文件 ("zip"、"tar"、"tar.gz"、"tar.zst")
+    pub output_format: Option<String>,
+    pub restic_binary: Option<String>,
+    pub env: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct GlobalRestoreConfig {
-    pub passwd: Option<String>,
+    #[serde(flatten)]
+    pub password: PasswordFields,
+    pub restic_binary: Option<String>,
+    pub env: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct RestoreConfigFile {
     #[serde(default)]
     global: GlobalRestoreConfig,
-    restore_jobs: HashMap<String, RestoreJob>,
+    restore_jobs: IndexMap<String, RestoreJob>,
 }
 
 #[derive(Debug, Clone)]
@@ -143,57 +762,158 @@ pub struct FinalRestoreConfig {
     pub job_name: String,
     pub repo: String,
     pub target: String,
-    pub passwd: String,
+    pub passwd: PasswordSource,
     pub snapshots: String,
     pub restore_path: String, // 新增: 指定快照中要恢复的子路径
+    /// 恢复为归档文件时的格式，`None` 表示恢复为普通文件
+    pub output_format: Option<ArchiveFormat>,
+    /// 该任务使用的 restic 可执行文件，`None` 时使用自动查找到的 restic
+    pub restic_binary: Option<String>,
+    /// 传给 restic 进程的额外环境变量
+    pub env: Vec<(String, String)>,
+}
+
+impl FinalRestoreConfig {
+    /// 该任务使用的 restic 可执行文件，未设置 `restic_binary` 时为 `default`
+    ///
+    /// `default` 为空表示没有找到全局的 restic，此时未设置 `restic_binary` 的任务返回 `Error::ResticMissing`。
+    pub fn restic_exe_path<'a>(&'a self, default: &'a str) -> Result<&'a str, Error> {
+        match self.restic_binary.as_deref() {
+            Some(binary) => Ok(binary),
+            None if default.is_empty() => Err(Error::ResticMissing),
+            None => Ok(default),
+        }
+    }
 }
 
 
 /// 解析批量恢复的 TOML 配置文件并验证
-pub fn parse_restore_toml(file_path: &str) -> Result<Vec<FinalRestoreConfig>, String> {
+pub fn parse_restore_toml(file_path: &str) -> Result<Vec<FinalRestoreConfig>, Error> {
     let toml_content = std::fs::read_to_string(file_path)
-        .map_err(|e| format!("读取恢复 TOML 文件 '{}' 失败: {}", file_path, e))?;
+        .map_err(|e| Error::config(file_path, "", "", format!("读取恢复 TOML 文件失败: {}", e)))?;
 
     let config_file: RestoreConfigFile = toml::from_str(&toml_content)
-        .map_err(|e| format!("恢复 TOML 文件解析失败: {}", e))?;
+        .map_err(|e| Error::config(file_path, "", "", format!("恢复 TOML 文件解析失败: {}", e)))?;
 
     let mut final_configs = Vec::new();
-    let mut error_messages = String::new();
+    let mut errors = Vec::new();
 
     for (job_name, job) in config_file.restore_jobs {
+        let passwd = match job.password.resolve(&config_file.global.password, &job_name) {
+            Ok(source) => source,
+            Err(e) => {
+                errors.push(e);
+                PasswordSource::Plain(String::new())
+            }
+        };
+        let output_format = match job.output_format.as_deref().map(ArchiveFormat::parse).transpose() {
+            Ok(format) => format,
+            Err(e) => {
+                errors.push(FieldError::new(&job_name, "output_format", format!("`output_format` 无效: {}。", e)));
+                None
+            }
+        };
         let final_cfg = FinalRestoreConfig {
             job_name: job_name.clone(),
-            repo: job.repo.unwrap_or_default(),
+            repo: job.repo.map(|repo| repo.trim().to_string()).unwrap_or_default(),
             target: job.target.unwrap_or_default(),
-            passwd: job.passwd.or(config_file.global.passwd.clone()).unwrap_or_default(),
+            passwd,
             snapshots: job.snapshots.unwrap_or_else(|| "latest".to_string()),
             restore_path: job.restore_path.unwrap_or_default(), // 新增
+            output_format,
+            restic_binary: job.restic_binary.or(config_file.global.restic_binary.clone()),
+            env: merge_env(config_file.global.env.as_ref(), job.env.as_ref()),
         };
+        errors.extend(validate_restic_env(&job_name, final_cfg.restic_binary.as_deref(), &final_cfg.env));
 
         // 验证必填字段
         if final_cfg.repo.is_empty() {
-            error_messages.push_str(&format!("[{}]: `repo` 字段不能为空。\n", job_name));
-        }
-        if !Path::new(&final_cfg.repo).exists() {
-             error_messages.push_str(&format!("[{}]: 仓库路径 '{}' 不存在。\n", job_name, final_cfg.repo));
+            errors.push(FieldError::new(&job_name, "repo", "`repo` 字段不能为空。"));
+        } else if !utils::is_remote_repo(&final_cfg.repo) && !Path::new(&final_cfg.repo).exists() {
+             errors.push(FieldError::new(&job_name, "repo", format!("仓库路径 '{}' 不存在。", final_cfg.repo)));
         }
         if final_cfg.target.is_empty() {
-            error_messages.push_str(&format!("[{}]: `target` 字段不能为空。\n", job_name));
-        }
-        if final_cfg.passwd.is_empty() {
-            error_messages.push_str(&format!("[{}]: `passwd` 字段不能为空 (全局或局部必须设置一个)。\n", job_name));
+            errors.push(FieldError::new(&job_name, "target", "`target` 字段不能为空。"));
         }
-
         final_configs.push(final_cfg);
     }
 
-    if !error_messages.is_empty() {
-        return Err(format!("恢复配置文件验证失败:\n{}", error_messages));
+    if !errors.is_empty() {
+        return Err(Error::ConfigInvalid { file: file_path.to_string(), errors });
     }
 
     if final_configs.is_empty() {
-        return Err("恢复配置文件中未找到任何有效的 [restore_jobs] 配置项。".to_string());
+        return Err(Error::config(file_path, "restore_jobs", "", "恢复配置文件中未找到任何有效的 [restore_jobs] 配置项。"));
     }
 
     Ok(final_configs)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(key: &str, priority: i64, depends_on: &[&str]) -> FinalConfig {
+        FinalConfig {
+            key_name: key.to_string(),
+            name: key.to_string(),
+            repo: None,
+            path: Vec::new(),
+            source: BackupSource::Paths,
+            tag: String::new(),
+            passwd: PasswordSource::Plain("pw".to_string()),
+            restic_home_path: String::new(),
+            merge: 0,
+            pack_size: 128,
+            compression: None,
+            retention: RetentionPolicy::default(),
+            exclude: ExcludeOptions::default(),
+            hooks: HookOptions::default(),
+            check_after_backup: false,
+            read_data_subset: None,
+            diff_after_backup: false,
+            auto_unlock_stale_after: None,
+            priority,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            schedule: None,
+            restic_binary: None,
+            env: Vec::new(),
+            init: InitOptions::default(),
+        }
+    }
+
+    fn keys(jobs: &[FinalConfig]) -> Vec<&str> {
+        jobs.iter().map(|job| job.key_name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_declaration_order_without_priorities() {
+        let ordered = order_by_dependencies(vec![job("c", 0, &[]), job("a", 0, &[]), job("b", 0, &[])]).unwrap();
+        assert_eq!(keys(&ordered), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn higher_priority_runs_first() {
+        let ordered = order_by_dependencies(vec![job("low", -1, &[]), job("default", 0, &[]), job("high", 5, &[]), job("also_high", 5, &[])]).unwrap();
+        assert_eq!(keys(&ordered), ["high", "also_high", "default", "low"]);
+    }
+
+    #[test]
+    fn dependencies_override_priority() {
+        let ordered = order_by_dependencies(vec![job("base", 0, &[]), job("urgent", 10, &["base"]), job("other", 1, &[])]).unwrap();
+        assert_eq!(keys(&ordered), ["other", "base", "urgent"]);
+    }
+
+    #[test]
+    fn chained_dependencies_run_in_order() {
+        let ordered = order_by_dependencies(vec![job("c", 0, &["b"]), job("b", 0, &["a"]), job("a", 0, &[])]).unwrap();
+        assert_eq!(keys(&ordered), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn reports_cycles_with_the_remaining_jobs() {
+        let err = order_by_dependencies(vec![job("free", 0, &[]), job("x", 0, &["y"]), job("y", 0, &["x"])]).unwrap_err();
+        assert_eq!(err.field, "depends_on");
+        assert!(err.message.contains("x, y"), "{}", err.message);
+        assert!(!err.message.contains("free"), "{}", err.message);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::backup;
+use crate::config::{self, FinalConfig};
+use crate::error::Error;
+use crate::history;
+use crate::progress::JobOutput;
+use crate::schedule::Schedule;
+use chrono::{DateTime, Local};
+use console::style;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 两次检查之间的最长间隔；按墙上时间判断是否到期，因此系统休眠唤醒后最多延迟这么久补跑
+const MAX_TICK: Duration = Duration::from_secs(30);
+
+fn log(output: &JobOutput, msg: impl std::fmt::Display) {
+    output.println(format!("{} {}", style(format!("[{}]", Local::now().format("%Y-%m-%d %H:%M:%S"))).dim(), msg));
+}
+
+/// 守护模式: 常驻运行，按每个任务的 `schedule` 定时执行备份
+///
+/// - 启动时根据运行历史计算下次运行时间，错过的运行 (例如程序未启动或系统休眠) 会尽快补跑一次；
+/// - 同一任务上一次运行尚未结束时跳过本次触发，不会重叠运行；
+/// - 其它任务正在使用同一仓库时，到期的任务会等仓库空闲后再运行；
+/// - 每次运行的结果都会写入运行历史。
+///
+/// 只有配置错误才会返回，正常情况下一直运行直到进程被结束。
+pub fn run_daemon(restic_exe_path: &str, config_path: &str, jobs: Option<usize>, output: &JobOutput) -> Result<(), Error> {
+    output.println(format!("\n{}\n", style("--- 守护模式 ---").bold().yellow()));
+    let plan = config::parse_toml(config_path)?;
+    let max_parallel = jobs.unwrap_or(plan.max_parallel);
+
+    let (scheduled, unscheduled): (Vec<FinalConfig>, Vec<FinalConfig>) = plan.jobs.into_iter().partition(|c| c.schedule.is_some());
+    if scheduled.is_empty() {
+        return Err("配置文件中没有设置 `schedule` 的任务，守护模式无事可做。".into());
+    }
+    for config in &unscheduled {
+        output.println(format!("{} 任务 {} 未设置 schedule，守护模式下不会运行。", style("i").blue(), config.key_name));
+    }
+
+    let now = Local::now();
+    let history = history::load().unwrap_or_default();
+    let mut next_due: HashMap<String, DateTime<Local>> = HashMap::new();
+    for config in &scheduled {
+        let schedule = config.schedule.as_ref().expect("已按 schedule 筛选");
+        let last_run = history
+            .iter()
+            .rev()
+            .find(|e| e.kind == "backup" && e.report.job == config.key_name)
+            .and_then(|e| DateTime::parse_from_rfc3339(&e.report.started_at).ok())
+            .map(|t| t.with_timezone(&Local));
+        // 有历史时从上次运行算起 (已经错过的运行会立即补跑)；没有历史时间隔类计划立即运行，cron 类等待下一次触发
+        let due = match (last_run, schedule) {
+            (Some(last), _) => schedule.next_after(last),
+            (None, Schedule::Interval(_)) => Some(now),
+            (None, Schedule::Cron(_)) => schedule.next_after(now),
+        };
+        match due {
+            Some(due) => {
+                output.println(format!("{} 任务 {} 下次运行: {}", style("→").dim(), config.key_name, due.format("%Y-%m-%d %H:%M")));
+                next_due.insert(config.key_name.clone(), due);
+            }
+            None => output.println(format!("{} 任务 {} 的运行计划永远不会触发，已忽略。", style("!").yellow(), config.key_name)),
+        }
+    }
+    output.println(format!("\n{} 守护进程已启动，按 Ctrl+C 退出。", style("✔").green()));
+
+    // 正在运行的任务键名，由后台线程在运行结束后移除
+    let running: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+
+    loop {
+        let now = Local::now();
+        let running_now = running.lock().unwrap().clone();
+        let busy_repos: HashSet<PathBuf> = scheduled
+            .iter()
+            .filter(|c| running_now.contains(&c.key_name))
+            .map(FinalConfig::repo_path)
+            .collect();
+
+        let mut batch: Vec<FinalConfig> = Vec::new();
+        for config in &scheduled {
+            let Some(due) = next_due.get(&config.key_name).copied() else { continue };
+            if due > now {
+                continue;
+            }
+            let schedule = config.schedule.as_ref().expect("已按 schedule 筛选");
+            if running_now.contains(&config.key_name) {
+                log(output, format!("任务 {} 的上一次运行尚未结束，跳过本次触发。", config.key_name));
+            } else if busy_repos.contains(&config.repo_path()) {
+                // 仓库正被其它任务使用，保持到期状态，下次检查时再试 (同一批次内共用仓库的任务由 run_toml_backup 依次执行)
+                continue;
+            } else {
+                batch.push(config.clone());
+            }
+            // 无论错过了多少次，都只补跑一次，下次运行从现在算起
+            match schedule.next_after(now) {
+                Some(next) => {
+                    next_due.insert(config.key_name.clone(), next);
+                }
+                None => {
+                    next_due.remove(&config.key_name);
+                }
+            }
+        }
+
+        if !batch.is_empty() {
+            start_batch(restic_exe_path, batch, max_parallel, &running, output);
+        }
+
+        let until_next = next_due
+            .values()
+            .min()
+            .and_then(|due| (*due - Local::now()).to_std().ok())
+            .unwrap_or(Duration::ZERO);
+        thread::sleep(until_next.clamp(Duration::from_secs(1), MAX_TICK));
+    }
+}
+
+/// 在后台线程中运行一批到期的任务
+fn start_batch(restic_exe_path: &str, mut batch: Vec<FinalConfig>, max_parallel: usize, running: &Arc<Mutex<HashSet<String>>>, output: &JobOutput) {
+    let keys: Vec<String> = batch.iter().map(|c| c.key_name.clone()).collect();
+    log(output, format!("开始运行到期的任务: {}", keys.join(", ")));
+
+    // 依赖的任务不在本批次中时视为已满足，由其自身的运行计划负责
+    for config in &mut batch {
+        config.depends_on.retain(|dep| keys.contains(dep));
+    }
+
+    running.lock().unwrap().extend(keys.iter().cloned());
+    let running = Arc::clone(running);
+    let restic_exe_path = restic_exe_path.to_string();
+    let output = output.clone();
+    thread::spawn(move || {
+        let reports = backup::run_toml_backup(&restic_exe_path, batch, max_parallel, &output);
+        let failed = reports.iter().filter(|r| !r.success).count();
+        let mut running = running.lock().unwrap();
+        for key in &keys {
+            running.remove(key);
+        }
+        drop(running);
+        match failed {
+            0 => log(&output, format!("{} 任务运行完成: {}", style("✔").green(), keys.join(", "))),
+            n => log(&output, format!("{} 任务运行完成: {} ({} 个失败)", style("✖").red(), keys.join(", "), n)),
+        }
+    });
+}
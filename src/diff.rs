@@ -0,0 +1,239 @@
+use crate::client::ResticClient;
+use crate::config::PasswordSource;
+use crate::error::Error;
+use crate::progress::{with_spinner, JobOutput};
+use crate::utils::{self, format_bytes};
+use console::style;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// 备份后的简短差异中每组最多列出的路径数
+const SHORT_LIST_LIMIT: usize = 5;
+
+/// 两个快照之间的差异 (`restic diff --json`)，按新增/删除/修改分组
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotDiff {
+    pub from: String,
+    pub to: String,
+    /// 设置后各分组只包含匹配的路径，`statistics` 仍是整个快照的统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_filter: Option<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<ModifiedPath>,
+    pub statistics: DiffStatistics,
+}
+
+/// 修改过的路径，`modifier` 为 restic 的标记: "M" 内容、"T" 类型、"U" 仅元数据
+#[derive(Debug, Clone, Serialize)]
+pub struct ModifiedPath {
+    pub path: String,
+    pub modifier: String,
+}
+
+/// restic 输出的差异统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffStatistics {
+    pub changed_files: u64,
+    pub added: DiffSize,
+    pub removed: DiffSize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffSize {
+    pub files: u64,
+    pub dirs: u64,
+    pub others: u64,
+    pub bytes: u64,
+}
+
+impl SnapshotDiff {
+    /// 只保留匹配 `filter` 的路径: 以 '/' 开头时按路径前缀匹配，否则按包含匹配
+    pub fn filtered(&self, filter: &str) -> SnapshotDiff {
+        let filter = utils::convert_to_restic_path(Path::new(filter.trim()));
+        let matches = |path: &str| if filter.starts_with('/') { path.starts_with(&filter) } else { path.contains(&filter) };
+        SnapshotDiff {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            path_filter: Some(filter.clone()),
+            added: self.added.iter().filter(|p| matches(p)).cloned().collect(),
+            removed: self.removed.iter().filter(|p| matches(p)).cloned().collect(),
+            modified: self.modified.iter().filter(|m| matches(&m.path)).cloned().collect(),
+            statistics: self.statistics.clone(),
+        }
+    }
+
+    /// 单行的人类可读摘要
+    pub fn describe(&self) -> String {
+        format!(
+            "新增 {} / 删除 {} / 修改 {} 项，新增数据 {}，移除数据 {}",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            format_bytes(self.statistics.added.bytes),
+            format_bytes(self.statistics.removed.bytes)
+        )
+    }
+}
+
+/// 比较两个快照，`from` 为较旧的快照
+pub fn run_diff(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, from: &str, to: &str, output: &JobOutput) -> Result<SnapshotDiff, Error> {
+    let client = ResticClient::new(restic_exe_path, repo_path, password.clone());
+    let stdout = with_spinner(output, || client.run(&["diff", "--json", from, to]))?;
+    parse_diff(from, to, &stdout)
+}
+
+/// 将 `restic diff --json` 的输出按新增/删除/修改分组
+fn parse_diff(from: &str, to: &str, stdout: &str) -> Result<SnapshotDiff, Error> {
+    let mut diff = SnapshotDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        path_filter: None,
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        statistics: DiffStatistics::default(),
+    };
+    // 每行一条 JSON 消息，最后一行是统计信息
+    for line in stdout.lines() {
+        let Ok(msg) = serde_json::from_str::<Value>(line) else { continue };
+        match msg["message_type"].as_str() {
+            Some("change") => {
+                let path = msg["path"].as_str().unwrap_or_default().to_string();
+                match msg["modifier"].as_str().unwrap_or_default() {
+                    "+" => diff.added.push(path),
+                    "-" => diff.removed.push(path),
+                    modifier => diff.modified.push(ModifiedPath { path, modifier: modifier.to_string() }),
+                }
+            }
+            Some("statistics") => {
+                diff.statistics = serde_json::from_value(msg).map_err(|e| format!("解析差异统计失败: {}", e))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(diff)
+}
+
+/// 查询快照的父快照 ID，首次备份的快照没有父快照
+fn parent_of(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, snapshot_id: &str, output: &JobOutput) -> Result<Option<String>, Error> {
+    let client = ResticClient::new(restic_exe_path, repo_path, password.clone());
+    let stdout = with_spinner(output, || client.run(&["cat", "snapshot", snapshot_id]))?;
+    let snapshot: Value = serde_json::from_str(&stdout).map_err(|e| format!("解析快照 {} 失败: {}", snapshot_id, e))?;
+    Ok(snapshot["parent"].as_str().map(|id| id.chars().take(8).collect()))
+}
+
+/// 备份完成后打印新快照与父快照之间的简短差异
+///
+/// 差异只用于展示，失败时返回的错误不应影响备份任务本身的结果。
+pub fn print_parent_diff(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, snapshot_id: &str, output: &JobOutput) -> Result<(), Error> {
+    let Some(parent) = parent_of(restic_exe_path, repo_path, password, snapshot_id, output)? else {
+        output.println(format!("{} 快照 {} 没有父快照，跳过差异比较。", style("i").blue(), snapshot_id));
+        return Ok(());
+    };
+    let diff = run_diff(restic_exe_path, repo_path, password, &parent, snapshot_id, output)?;
+    output.println(format!("{} 与父快照 {} 相比: {}", style("→").dim(), parent, diff.describe()));
+    for line in group_lines(&diff, SHORT_LIST_LIMIT) {
+        output.println(line);
+    }
+    Ok(())
+}
+
+/// 将差异按分组格式化为多行文本，每组最多 `limit` 条
+pub fn group_lines(diff: &SnapshotDiff, limit: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut push_group = |title: String, paths: Vec<String>| {
+        if paths.is_empty() {
+            return;
+        }
+        lines.push(format!("  {}", title));
+        for path in paths.iter().take(limit) {
+            lines.push(format!("    {}", path));
+        }
+        if paths.len() > limit {
+            lines.push(format!("    {}", style(format!("... 还有 {} 项", paths.len() - limit)).dim()));
+        }
+    };
+    push_group(
+        format!("{} 新增 ({} 项，{})", style("+").green(), diff.added.len(), format_bytes(diff.statistics.added.bytes)),
+        diff.added.clone(),
+    );
+    push_group(
+        format!("{} 删除 ({} 项，{})", style("-").red(), diff.removed.len(), format_bytes(diff.statistics.removed.bytes)),
+        diff.removed.clone(),
+    );
+    push_group(
+        format!("{} 修改 ({} 项)", style("M").yellow(), diff.modified.len()),
+        diff.modified.iter().map(|m| format!("{} {}", style(&m.modifier).dim(), m.path)).collect(),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"repository 1234 opened (version 2, compression level auto)
+{"message_type":"change","path":"/data/new.txt","modifier":"+"}
+{"message_type":"change","path":"/data/sub/added.bin","modifier":"+"}
+{"message_type":"change","path":"/data/old.txt","modifier":"-"}
+{"message_type":"change","path":"/data/app.conf","modifier":"M"}
+{"message_type":"change","path":"/data/sub","modifier":"U"}
+{"message_type":"statistics","source_snapshot":"aaaa1111","target_snapshot":"bbbb2222","changed_files":3,"added":{"files":2,"dirs":0,"others":0,"data_blobs":2,"tree_blobs":1,"bytes":2048},"removed":{"files":1,"dirs":0,"others":0,"data_blobs":1,"tree_blobs":0,"bytes":100}}"#;
+
+    fn sample() -> SnapshotDiff {
+        parse_diff("aaaa1111", "bbbb2222", OUTPUT).unwrap()
+    }
+
+    fn paths(modified: &[ModifiedPath]) -> Vec<&str> {
+        modified.iter().map(|m| m.path.as_str()).collect()
+    }
+
+    #[test]
+    fn groups_changes_by_modifier() {
+        let diff = sample();
+        assert_eq!(diff.added, ["/data/new.txt", "/data/sub/added.bin"]);
+        assert_eq!(diff.removed, ["/data/old.txt"]);
+        assert_eq!(paths(&diff.modified), ["/data/app.conf", "/data/sub"]);
+        assert_eq!(diff.modified[1].modifier, "U");
+        assert_eq!(diff.statistics.changed_files, 3);
+        assert_eq!(diff.statistics.added.bytes, 2048);
+        assert_eq!(diff.describe(), "新增 2 / 删除 1 / 修改 2 项，新增数据 2.00 KiB，移除数据 100 B");
+    }
+
+    #[test]
+    fn filters_by_prefix_or_substring() {
+        let diff = sample();
+        let prefix = diff.filtered("/data/sub");
+        assert_eq!(prefix.path_filter.as_deref(), Some("/data/sub"));
+        assert_eq!(prefix.added, ["/data/sub/added.bin"]);
+        assert!(prefix.removed.is_empty());
+        assert_eq!(paths(&prefix.modified), ["/data/sub"]);
+        // 统计仍是整个快照的
+        assert_eq!(prefix.statistics.added.bytes, 2048);
+
+        let contains = diff.filtered(" .txt ");
+        assert_eq!(contains.added, ["/data/new.txt"]);
+        assert_eq!(contains.removed, ["/data/old.txt"]);
+        assert!(contains.modified.is_empty());
+
+        assert!(diff.filtered("/other").added.is_empty());
+    }
+
+    #[test]
+    fn limits_each_group() {
+        let lines = group_lines(&sample(), 1);
+        assert_eq!(lines.len(), 8, "{:#?}", lines);
+        assert!(lines[0].contains("新增 (2 项，2.00 KiB)"), "{}", lines[0]);
+        assert_eq!(lines[1], "    /data/new.txt");
+        assert!(lines[2].contains("还有 1 项"), "{}", lines[2]);
+        assert!(lines[3].contains("删除 (1 项，100 B)"), "{}", lines[3]);
+        assert!(lines[7].contains("还有 1 项"), "{}", lines[7]);
+
+        let empty = parse_diff("a", "b", "").unwrap();
+        assert!(group_lines(&empty, 5).is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::report;
+use crate::version;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// xcompress 的错误类型
+///
+/// restic 失败时根据其输出归类为具体的变体，调用方可以据此匹配、映射退出码 ([`Error::exit_code`])
+/// 并向用户给出处理建议 ([`Error::hint`])。无法归类的 restic 失败保留完整的输出。
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// 仓库密码错误
+    WrongPassword,
+    /// 仓库不存在或路径不是 restic 仓库
+    RepoNotFound(String),
+    /// 仓库被其它 restic 进程锁定
+    RepoLocked(String),
+    /// 读写仓库或备份源时权限不足
+    PermissionDenied(String),
+    /// 目标磁盘空间不足
+    DiskFull(String),
+    /// 访问远程仓库时的网络或存储后端错误
+    Backend(String),
+    /// restic 版本过旧，不支持所需的参数或命令
+    ResticTooOld(String),
+    /// 未找到 restic 可执行文件
+    ResticMissing,
+    /// 配置文件或命令行参数无效，逐项列出出错的字段 (命令行参数时 `file` 为空)
+    ConfigInvalid { file: String, errors: Vec<FieldError> },
+    /// 无法归类的 restic 失败
+    Restic { stdout: String, stderr: String },
+    /// 其它错误 (交互、文件读写等)
+    Other(String),
+    /// 重新描述过的错误，类别与处理建议取自 `source`
+    Context { message: String, source: Box<Error> },
+}
+
+/// 错误的类别，用于运行报告与历史记录中的机器可读字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    WrongPassword,
+    RepoNotFound,
+    RepoLocked,
+    PermissionDenied,
+    DiskFull,
+    Backend,
+    ResticTooOld,
+    ResticMissing,
+    ConfigInvalid,
+    Restic,
+    Other,
+}
+
+/// 配置文件中一个字段的验证错误
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// 所在的配置节，例如任务键名或 "global_config" (命令行参数时为子命令名)；整个文件的问题时为空
+    pub section: String,
+    /// 出错的字段 (或命令行参数)，涉及多个字段或整个配置节时为空
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(section: &str, field: &str, message: impl Into<String>) -> Self {
+        Self { section: section.to_string(), field: field.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.section.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "[{}]: {}", self.section, self.message)
+        }
+    }
+}
+
+impl Error {
+    /// 根据 restic 失败时的输出归类错误
+    pub fn from_restic_output(stdout: &str, stderr: &str) -> Error {
+        let lower = stderr.to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+        let detail = restic_detail(stderr);
+
+        if has(&["wrong password or no key found"]) {
+            Error::WrongPassword
+        } else if has(&["repository is already locked"]) {
+            Error::RepoLocked(detail)
+        } else if has(&["is there a repository at the given location?", "repository does not exist", "unable to open config file"]) {
+            Error::RepoNotFound(detail)
+        } else if has(&["no space left on device", "not enough space on the disk", "disk quota exceeded"]) {
+            Error::DiskFull(detail)
+        } else if has(&["permission denied", "access is denied", "operation not permitted"]) {
+            Error::PermissionDenied(detail)
+        } else if has(&[
+            "connection refused",
+            "connection reset",
+            "no such host",
+            "i/o timeout",
+            "network is unreachable",
+            "context deadline exceeded",
+            "tls handshake",
+            "server response unexpected",
+        ]) {
+            Error::Backend(detail)
+        } else if has(&["unknown flag", "unknown shorthand flag", "unknown command"]) {
+            Error::ResticTooOld(detail)
+        } else {
+            Error::Restic { stdout: stdout.to_string(), stderr: stderr.to_string() }
+        }
+    }
+
+    /// 配置文件 `file` 中只有一处错误时的简便写法
+    pub fn config(file: &str, section: &str, field: &str, message: impl Into<String>) -> Error {
+        Error::ConfigInvalid { file: file.to_string(), errors: vec![FieldError::new(section, field, message)] }
+    }
+
+    /// 用 `f` 根据原错误生成新的错误信息 (例如加上任务的上下文)，错误类别保持不变
+    pub fn with_message(self, f: impl FnOnce(&Error) -> String) -> Error {
+        Error::Context { message: f(&self), source: Box::new(self) }
+    }
+
+    /// 去掉所有说明后的原始错误
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self.root() {
+            Error::WrongPassword => ErrorKind::WrongPassword,
+            Error::RepoNotFound(_) => ErrorKind::RepoNotFound,
+            Error::RepoLocked(_) => ErrorKind::RepoLocked,
+            Error::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            Error::DiskFull(_) => ErrorKind::DiskFull,
+            Error::Backend(_) => ErrorKind::Backend,
+            Error::ResticTooOld(_) => ErrorKind::ResticTooOld,
+            Error::ResticMissing => ErrorKind::ResticMissing,
+            Error::ConfigInvalid { .. } => ErrorKind::ConfigInvalid,
+            Error::Restic { .. } => ErrorKind::Restic,
+            Error::Other(_) | Error::Context { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// 该错误对应的进程退出码
+    pub fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+
+    /// 给用户的处理建议，没有明确建议时返回 `None`
+    pub fn hint(&self) -> Option<String> {
+        self.kind().hint()
+    }
+}
+
+impl ErrorKind {
+    /// 该类错误对应的进程退出码
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::WrongPassword => report::EXIT_WRONG_PASSWORD,
+            ErrorKind::RepoNotFound => report::EXIT_REPO_NOT_FOUND,
+            ErrorKind::RepoLocked => report::EXIT_REPO_LOCKED,
+            ErrorKind::PermissionDenied => report::EXIT_PERMISSION_DENIED,
+            ErrorKind::DiskFull => report::EXIT_DISK_FULL,
+            ErrorKind::Backend => report::EXIT_BACKEND_ERROR,
+            ErrorKind::ResticTooOld => report::EXIT_RESTIC_TOO_OLD,
+            ErrorKind::ResticMissing => report::EXIT_RESTIC_MISSING,
+            ErrorKind::ConfigInvalid => report::EXIT_CONFIG_ERROR,
+            ErrorKind::Restic | ErrorKind::Other => report::EXIT_PARTIAL_FAILURE,
+        }
+    }
+
+    /// 给用户的处理建议，没有明确建议时返回 `None`
+    pub fn hint(self) -> Option<String> {
+        let hint = match self {
+            ErrorKind::WrongPassword => Some("请检查 passwd / passwd_file / passwd_command / passwd_env 设置，或重新输入密码。"),
+            ErrorKind::RepoNotFound => Some("请确认仓库路径正确；备份时不存在的仓库会自动初始化。"),
+            ErrorKind::RepoLocked => Some("请确认没有其它 restic 进程正在使用该仓库；若锁已过期，可设置 auto_unlock_stale_after 自动移除，或执行 `restic -r <仓库> unlock`。"),
+            ErrorKind::PermissionDenied => Some("请检查当前用户对仓库目录与备份源的读写权限，必要时以管理员身份运行。"),
+            ErrorKind::DiskFull => Some("请清理目标磁盘空间，或通过 keep_* 与 prune 清理旧快照后重试。"),
+            ErrorKind::Backend => Some("请检查网络连接与仓库地址，稍后重试。"),
+            ErrorKind::ResticTooOld => {
+                return Some(format!("请按上面的要求升级 restic (最低支持 {}，推荐 {} 或更高版本)。", version::MIN_SUPPORTED, version::RECOMMENDED));
+            }
+            ErrorKind::ResticMissing => Some("请将 restic 可执行文件 (Linux 下通常为 'restic'，Windows 下为 'restic.exe') 放置于本程序同目录下，或将其路径添加到系统 PATH 环境变量中。"),
+            ErrorKind::ConfigInvalid => Some("请按上面列出的字段修改配置，配置说明见主菜单中的「查看帮助」。"),
+            ErrorKind::Restic | ErrorKind::Other => None,
+        };
+        hint.map(str::to_string)
+    }
+}
+
+/// restic 标准错误中最能说明问题的一行: 优先取 "Fatal:" 开头的行，否则取最后一个非空行
+fn restic_detail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let line = lines
+        .iter()
+        .find(|l| l.starts_with("Fatal:"))
+        .or(lines.last())
+        .copied()
+        .unwrap_or_default();
+    line.trim_start_matches("Fatal:").trim().to_string()
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 附带 restic 的原始说明，便于排查
+        let with_detail = |f: &mut fmt::Formatter<'_>, summary: &str, detail: &str| {
+            if detail.is_empty() {
+                write!(f, "{}", summary)
+            } else {
+                write!(f, "{} ({})", summary, detail)
+            }
+        };
+        match self {
+            Error::WrongPassword => write!(f, "密码错误。"),
+            Error::RepoNotFound(detail) => with_detail(f, "仓库路径无效或不存在。", detail),
+            Error::RepoLocked(detail) => with_detail(f, "仓库已被锁定。", detail),
+            Error::PermissionDenied(detail) => with_detail(f, "权限不足。", detail),
+            Error::DiskFull(detail) => with_detail(f, "磁盘空间不足。", detail),
+            Error::Backend(detail) => with_detail(f, "无法访问仓库存储 (网络或后端错误)。", detail),
+            Error::ResticTooOld(detail) => with_detail(f, "restic 版本过旧，不支持所需的功能。", detail),
+            Error::ResticMissing => write!(f, "未找到 restic 环境。"),
+            Error::ConfigInvalid { file, errors } => {
+                if file.is_empty() {
+                    write!(f, "命令行参数有误:")?;
+                } else {
+                    write!(f, "配置文件 '{}' 有误:", file)?;
+                }
+                for error in errors {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
+            Error::Restic { stdout, stderr } => write!(
+                f,
+                "Restic 命令执行失败:\n--- 标准输出 ---\n{}\n--- 标准错误 ---\n{}",
+                stdout, stderr
+            ),
+            Error::Other(message) => write!(f, "{}", message),
+            Error::Context { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Other(message.to_string())
+    }
+}
@@ -0,0 +1,13 @@
+use crate::client::ResticClient;
+use crate::config::{PasswordSource, RetentionPolicy};
+use crate::error::Error;
+use crate::lock::UnlockPolicy;
+use crate::progress::{with_spinner, JobOutput};
+use console::style;
+
+/// 按保留策略执行 `restic forget` (可选 `--prune`)，返回被移除的快照短 ID
+pub fn run_forget(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, policy: &RetentionPolicy, unlock: UnlockPolicy, output: &JobOutput) -> Result<Vec<String>, Error> {
+    output.println(format!("{} 正在按保留策略清理旧快照 ({}) ...", style("i").blue(), policy.to_args().join(" ")));
+    let client = ResticClient::new(restic_exe_path, repo_path, password.clone()).with_unlock_policy(unlock);
+    with_spinner(output, || client.forget(policy))
+}
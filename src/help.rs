@@ -25,12 +25,19 @@ pub fn print_help_info() {
     println!("      {} xcompress.exe {}", cmd("  >"), path("my_backup_jobs.toml"));
     println!("      程序将自动读取指定的 toml 文件并执行所有备份任务。");
 
+    println!("\n    - 使用子命令在脚本 / 计划任务中运行 (不弹出任何交互提示):");
+    println!("      {} xcompress.exe {}", cmd("  >"), path("batch-backup backup_config.toml --yes"));
+    println!("      完整的子命令列表请运行 {} 查看。", cmd("xcompress.exe help"));
+
     println!("\n  {}", header("2. 交互式菜单模式 (无参数启动)"));
     println!("    直接运行 {} 将进入主菜单，提供以下选项：", cmd("xcompress.exe"));
     println!("    - {}：通过交互式问答备份单个文件/目录。程序会扫描并让您选择仓库。", opt("备份 (Compress)"));
-    println!("    - {}：通过交互式问答恢复一个仓库中的快照。", opt("恢复 (Decompress)"));
+    println!("    - {}：通过交互式问答恢复一个仓库中的快照，也可以浏览快照的目录树只恢复选中的文件，或打包为 zip / tar / tar.gz / tar.zst 归档文件。", opt("恢复 (Decompress)"));
     println!("    - {}：选择一个 backup_config.toml 文件进行批量备份。", opt("批量备份 (Batch Backup)"));
     println!("    - {}：选择一个 restore_config.toml 文件进行批量恢复。", opt("批量恢复 (Batch Restore)"));
+    println!("    - {}：运行 restic check 校验仓库完整性，可选读取部分或全部数据。", opt("校验仓库 (Check)"));
+    println!("    - {}：选择仓库中的两个快照，按新增/删除/修改分组列出差异，可按路径筛选。", opt("比较快照 (Diff)"));
+    println!("    - {}：查看以往备份/恢复的运行记录 (保存在程序目录下的 xcompress_history.jsonl)，可按任务筛选并查看详情。", opt("历史记录 (History)"));
     println!("    - {}：显示当前帮助信息。", opt("查看帮助 (View Help)"));
     println!("    - {}：退出程序。", opt("退出 (Exit)"));
     println!("\n    {}", style("注意：如果在程序目录下存在 backup_config.toml 文件，无参数启动时会优先执行批量备份，而不是显示主菜单。").dim());
@@ -46,29 +53,95 @@ pub fn print_help_info() {
     tag = "daily"
     # pack_size: 128 (推荐) = 文件数少、压缩率最高、适合网盘；16 = 碎片多、本地性能最高。
     pack_size = 128 
+    # repo_version = 2    # 新建仓库时的格式版本 (局部配置优先): 1 / 2 / "latest" / "stable"；1 不支持压缩
+    # compression = "auto"  # 压缩模式 (局部配置优先): auto (默认) / max (压缩率最高，较慢) / off (不压缩)；格式 v1 的旧仓库不支持 max
+    # max_parallel = 2    # 最多同时执行的任务数 (默认 1)；同一仓库的任务仍会依次执行
+    # 保留策略 (可选，局部配置优先)：备份成功后自动执行 restic forget
+    # keep_last = 10      # 保留最近 10 个快照
+    # keep_daily = 7      # 保留最近 7 天每天最后一个快照
+    # keep_weekly = 4
+    # keep_monthly = 12
+    # keep_yearly = 3
+    # keep_tag = "keep"   # 带有该标签的快照永远保留
+    # prune = true        # 同时删除不再被引用的数据，释放空间
+    # 备份后校验 (可选，局部配置优先)
+    # check_after_backup = true   # 备份成功后执行 restic check
+    # read_data_subset = "5%"     # 校验时同时读取部分数据 (也可写 "1/10" 或 "2G")
+    # diff_after_backup = true    # 备份成功后列出与上一个快照 (父快照) 相比的简短差异
+    # 仓库被锁定时 (可选，局部配置优先)：列出持有锁的主机与进程
+    # auto_unlock_stale_after = "2h"  # 所有锁都超过该时长 (至少 10m) 未刷新时自动移除并重试一次；交互式操作会询问
+    # 钩子命令 (可选，局部配置优先)：通过系统 shell 执行
+    # pre_command = "pg_dump mydb > D:\\dumps\\mydb.sql"  # 备份前执行
+    # post_command = "net start MyService"       # 备份结束后执行 (无论成功与否)
+    # on_success = "echo ok"                     # 任务成功时执行
+    # on_failure = "notify.bat"                  # 任务失败时执行
+    # hook_timeout = 600                         # 单个钩子的超时秒数，0 表示不限制
+    # abort_on_pre_failure = true                # pre_command 失败时放弃本次备份 (默认 true)
+    # 钩子可读取环境变量: XCOMPRESS_HOOK, XCOMPRESS_JOB, XCOMPRESS_JOB_NAME, XCOMPRESS_REPO,
+    # XCOMPRESS_PATHS, XCOMPRESS_STATUS (running/success/failure), XCOMPRESS_SNAPSHOT_IDS, XCOMPRESS_ERROR
+    # 排除规则 (可选，局部配置优先)
+    # exclude = ["node_modules", "target", "*.tmp"]  # 排除模式 (同 restic --exclude)
+    # iexclude = ["*.LOG"]                         # 忽略大小写的排除模式
+    # exclude_file = ["D:\\excludes.txt"]           # 从文件读取排除模式
+    # exclude_if_present = [".nobackup"]           # 目录中存在该文件时整个目录不备份
+    # exclude_caches = true                        # 排除包含 CACHEDIR.TAG 的缓存目录
+    # exclude_larger_than = "2G"                   # 排除大于该大小的文件
+    # restic 程序与环境变量 (可选，局部配置优先)
+    # restic_binary = "D:\\tools\\restic_0.17.3.exe"  # 使用指定的 restic，而不是自动查找到的版本
+    # env = {{ RESTIC_CACHE_DIR = "E:\\restic_cache", GOMAXPROCS = "4" }}  # 传给 restic 的环境变量，与局部的 env 合并
 
     # 备份任务配置 (可以有多个)
     [config.project_A] # "project_A" 是任务的唯一标识
     name = "Project_A_Backup" # 仓库目录名，会拼接在 restic_home_path 后面
     path = ["D:\\code\\projectA", "C:\\docs\\projectA_docs"] # 需要备份的路径列表
-    # merge = 1 # 设置为1时，会先将所有 path 的内容复制到临时目录再整体备份
-    # merge_name = "merged_projA" # merge=1 时的临时目录名前缀
+    # merge = 1 # 设置为1时，所有 path 作为同一个快照备份 (不复制文件，快照保留原始路径)
 
     [config.photos]
     name = "My_Photos"
     path = ["E:\\Photos"]
     passwd = "photo_password_123" # 单独为此任务设置密码
+    # env = {{ RESTIC_COMPRESSION = "max" }}  # 只对此任务生效，同名变量覆盖全局 env
+
+    [config.web]
+    # repo (或 repository) 直接指定仓库位置，可以是本地路径或远程 URL，设置后不使用 restic_home_path
+    # 支持 rest:、sftp:、s3:、b2:、azure:、gs:、swift:、rclone: 等 restic 后端；远程仓库不存在时自动初始化
+    repo = "rest:http://192.168.1.10:8000/web"
+    path = ["D:\\www"]
+    # 新建仓库时复制另一个仓库的分块参数，之后用 restic copy 在两者之间复制快照时可以复用数据块 (二选一):
+    # copy_chunker_params_from = "project_A"       # 来源为另一个任务的仓库 (使用该任务的密码，并自动排在该任务之后)
+    # from_repo = "E:\\offsite_repos\\www"         # 来源为任意仓库 (本地路径或远程 URL)
+    # from_passwd_file = "E:\\keys\\offsite.txt"   # from_repo 的密码，也可用 from_passwd / from_passwd_command / from_passwd_env；未设置时使用本任务的密码
+
+    [config.deliveries]
+    name = "Deliveries"
+    # source = "archive": path 是 .zip / .tar / .tar.gz / .tar.zst 归档或存放归档的目录，
+    # 每个归档解压到固定的临时目录后备份为一个快照，并以归档文件名作为标签
+    source = "archive"
+    path = ["E:\\deliveries"]
+
+    # 任务按配置文件中的声明顺序执行，也可以用以下字段调整:
+    # priority = 10                 # 数值越大越先执行 (默认 0)
+    # depends_on = ["project_A"]    # 等这些任务成功后再执行；依赖任务失败时本任务会被跳过
+    # 守护模式 (xcompress daemon backup_config.toml) 下的运行计划:
+    # schedule = "every 6h"         # 固定间隔 (s/m/h/d，可组合如 "1h30m")
+    # schedule = "0 3 * * *"        # cron 表达式: 分 时 日 月 周；也可写 "@daily" / "@weekly" 等
+
+    # 不想在配置文件中保存明文密码时，可改用以下任意一种 (同一层级只能设置一个):
+    # passwd_file = "D:\\secrets\\repo.txt"    # 从文件读取 (restic --password-file)
+    # passwd_command = "pass show restic/repo"  # 从命令输出读取 (restic --password-command)
+    # passwd_env = "PHOTOS_RESTIC_PASSWORD"     # 从环境变量读取
     "#);
 
     println!("\n  {}", header("恢复配置 (例如 restore_config.toml)"));
     println!(r#"
     # 全局配置 (可选)
     [global]
-    passwd = "default_password"
+    passwd = "default_password" # 也可使用 passwd_file / passwd_command / passwd_env
+    # restic_binary 与 env 的写法同备份配置，也可以在单个恢复任务中设置
 
     # 恢复任务配置 (可以有多个)
     [restore_jobs.restore_projA]
-    repo = "D:\\all_my_restic_repos\\Project_A_Backup" # 【必填】要从哪个仓库恢复
+    repo = "D:\\all_my_restic_repos\\Project_A_Backup" # 【必填】要从哪个仓库恢复 (也可以是 "rest:http://..." 等远程 URL)
     target = "D:\\restored_files\\project_A"      # 【必填】恢复到哪里
     # 【可选】要恢复的快照ID。可选值为:
     # "latest" (默认), "all", 或 "id1,id2,id3" (短ID列表)
@@ -83,10 +156,56 @@ pub fn print_help_info() {
     target = "D:\\restored_files\\photos"
     snapshots = "a1b2c3d4, e5f6g7h8"
     # 如果不指定 restore_path，则会按 restic 默认行为恢复整个快照（带完整路径）
+
+    [restore_jobs.handover_zip]
+    repo = "D:\\all_my_restic_repos\\Project_A_Backup"
+    target = "D:\\handover"  # 归档文件保存目录，文件名为 "任务名_快照ID.扩展名"
+    # 【可选】不恢复为散落的文件，而是打包为单个归档文件: "zip"、"tar"、"tar.gz"、"tar.zst"
+    output_format = "zip"
     "#);
 
     println!("\n{}", header("==================================================================="));
     println!("\n{}", style("按 Enter 键返回主菜单...").dim());
     let mut buffer = String::new();
     io::stdin().read_line(&mut buffer).unwrap();
-}
\ No newline at end of file
+}
+
+/// 打印命令行子命令用法 (`xcompress help`)，不等待输入
+pub fn print_cli_usage() {
+    let header = |s| style(s).yellow().bold();
+    let cmd = |s| style(s).cyan();
+    let opt = |s| style(s).green();
+
+    println!("{}", header("用法: xcompress [子命令] [参数] [--yes]"));
+    println!("\n{}", header("子命令:"));
+    println!("  {}  备份一个或多个路径", cmd("backup <路径>... --repo <仓库> <密码参数> [--tag <标签>] [--pack-size <16-128>]"));
+    println!("  {}  恢复快照 (默认 latest)", cmd("restore --repo <仓库> --target <目录> <密码参数> [--snapshot <ID>] [--path <子路径>] [--format <zip|tar|tar.gz|tar.zst>]"));
+    println!("  {}  按配置文件批量备份，--jobs 指定最多同时执行的任务数", cmd("batch-backup <backup_config.toml> [--jobs <N>]"));
+    println!("  {}  按配置文件批量恢复", cmd("batch-restore <restore_config.toml>"));
+    println!("  {}  列出仓库中的快照", cmd("snapshots --repo <仓库> <密码参数>"));
+    println!("  {}  校验仓库完整性", cmd("check --repo <仓库> <密码参数> [--read-data-subset <5%|1/10|2G>]"));
+    println!("  {}  以 JSON 输出两个快照之间的差异 (未指定 --output 时输出到标准输出，其余输出改写到标准错误，此时报告须用 --report-file 写入文件)", cmd("diff --repo <仓库> <密码参数> <旧快照> <新快照> [--path <路径>] [--output <文件>]"));
+    println!("  {}  常驻运行，按任务的 schedule 定时备份 (错过的运行会补跑一次)", cmd("daemon <backup_config.toml> [--jobs <N>]"));
+    println!("  {}  查看运行历史 (默认最近 20 条)", cmd("history [任务键名] [--kind backup|restore] [--success|--failed] [--limit <N>] [--details]"));
+    println!("  {}  显示本帮助", cmd("help"));
+    println!("\n{}", header("密码参数 (任选其一):"));
+    println!("  {}  从环境变量读取密码 (推荐)", opt("--passwd-env <变量名>"));
+    println!("  {}  从文件第一行读取密码 (由 restic 读取，推荐)", opt("--passwd-file <文件>"));
+    println!("  {}  运行命令获取密码 (由 restic 执行)", opt("--passwd-command <命令>"));
+    println!(
+        "  {}  直接指定密码 {}",
+        opt("--passwd <密码>"),
+        style("(不安全: 密码会出现在进程列表与 shell 历史中，请改用 --passwd-env 或 --passwd-file)").yellow()
+    );
+    println!("\n{}", header("通用参数:"));
+    println!("  {}  非交互模式: 不弹出任何提示，结束时不等待回车", opt("-y, --yes, --no-pause"));
+    println!("  {}  运行结束后在标准输出打印 JSON 格式的任务报告 (其余输出改写到标准错误)", opt("--report json"));
+    println!("  {}  将 JSON 报告写入文件", opt("--report-file <文件>"));
+    println!("\n{}", header("环境变量:"));
+    println!("  {}  指定 restic 可执行文件，优先于程序目录与系统 PATH (配置中的 restic_binary 仍然优先)", opt("XCOMPRESS_RESTIC"));
+    println!("\n{}", header("退出码:"));
+    println!("  0 全部成功 / 1 有任务失败 / 2 参数或配置错误 / 3 未找到 restic / 4 仓库密码错误");
+    println!("  5 仓库不存在 / 6 仓库被锁定 / 7 权限不足 / 8 磁盘空间不足 / 9 网络或存储后端错误 / 10 restic 版本过旧");
+    println!("  (多个任务失败时取第一个能归类的错误；JSON 报告中的 error_kind 字段给出每个任务的错误类别)");
+    println!("\n不带子命令时，xcompress 保持旧版行为: 无参数显示主菜单，传入 toml 文件执行批量备份，传入仓库进入恢复，传入其它路径进入交互式备份。");
+}
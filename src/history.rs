@@ -0,0 +1,169 @@
+use crate::progress::JobOutput;
+use crate::report::JobReport;
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// 运行历史文件名，保存在程序所在目录，每行一条 JSON 记录
+const HISTORY_FILE: &str = "xcompress_history.jsonl";
+
+/// 未指定数量时列出的最近记录数
+const DEFAULT_LIMIT: usize = 20;
+
+/// 一条运行历史: 任务类型加上该任务的执行报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// "backup" 或 "restore"
+    pub kind: String,
+    #[serde(flatten)]
+    pub report: JobReport,
+}
+
+/// 历史记录的筛选条件，未设置的条件不参与筛选
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// 任务键名 (精确匹配)
+    pub job: Option<String>,
+    pub kind: Option<String>,
+    /// `Some(true)` 只看成功的记录，`Some(false)` 只看失败的记录
+    pub success: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    /// 从按写入顺序排列的 `entries` 中筛选，最新的在前
+    fn apply(&self, entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = entries.into_iter().filter(|e| self.matches(e)).collect();
+        entries.reverse();
+        entries.truncate(self.limit.unwrap_or(DEFAULT_LIMIT));
+        entries
+    }
+
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.job.as_ref().is_none_or(|job| &entry.report.job == job)
+            && self.kind.as_ref().is_none_or(|kind| &entry.kind == kind)
+            && self.success.is_none_or(|success| entry.report.success == success)
+    }
+}
+
+/// 历史文件路径: 程序所在目录，获取失败时使用当前目录
+pub fn history_path() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(HISTORY_FILE)))
+        .unwrap_or_else(|| PathBuf::from(HISTORY_FILE))
+}
+
+/// 追加任务结果到运行历史
+///
+/// 历史只是辅助信息，写入失败时仅通过 `output` 给出警告，不影响任务本身的结果。
+pub fn record(kind: &str, reports: &[JobReport], output: &JobOutput) {
+    if reports.is_empty() {
+        return;
+    }
+    if let Err(e) = append(kind, reports) {
+        output.println(format!("{} 写入运行历史失败: {}", style("!").yellow(), e));
+    }
+}
+
+fn append(kind: &str, reports: &[JobReport]) -> Result<(), String> {
+    let path = history_path();
+    let mut lines = String::new();
+    for report in reports {
+        let entry = HistoryEntry { kind: kind.to_string(), report: report.clone() };
+        lines.push_str(&serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    file.write_all(lines.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// 读取全部历史记录 (按写入顺序)，无法解析的行会被跳过
+pub fn load() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("读取运行历史 '{}' 失败: {}", path.display(), e))?;
+    Ok(parse_entries(&content))
+}
+
+fn parse_entries(content: &str) -> Vec<HistoryEntry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// 按条件筛选历史记录，最新的在前
+pub fn query(filter: &HistoryFilter) -> Result<Vec<HistoryEntry>, String> {
+    Ok(filter.apply(load()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn entry(kind: &str, job: &str, success: bool) -> HistoryEntry {
+        let result = if success { Ok((String::new(), vec![format!("{}-snap", job)])) } else { Err("boom".into()) };
+        HistoryEntry { kind: kind.to_string(), report: JobReport::new(job, "/repo", Instant::now(), result) }
+    }
+
+    fn jobs(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.report.job.as_str()).collect()
+    }
+
+    fn sample() -> Vec<HistoryEntry> {
+        vec![
+            entry("backup", "a", true),
+            entry("backup", "b", false),
+            entry("restore", "a", true),
+            entry("backup", "a", false),
+        ]
+    }
+
+    #[test]
+    fn lists_newest_first_with_limit() {
+        assert_eq!(jobs(&HistoryFilter::default().apply(sample())), ["a", "a", "b", "a"]);
+        let latest = HistoryFilter { limit: Some(2), ..Default::default() }.apply(sample());
+        assert_eq!(latest.iter().map(|e| e.kind.as_str()).collect::<Vec<_>>(), ["backup", "restore"]);
+
+        let many: Vec<HistoryEntry> = (0..DEFAULT_LIMIT + 5).map(|i| entry("backup", &i.to_string(), true)).collect();
+        let listed = HistoryFilter::default().apply(many);
+        assert_eq!(listed.len(), DEFAULT_LIMIT);
+        assert_eq!(listed[0].report.job, (DEFAULT_LIMIT + 4).to_string());
+    }
+
+    #[test]
+    fn combines_conditions() {
+        let by_job = HistoryFilter { job: Some("a".to_string()), ..Default::default() }.apply(sample());
+        assert_eq!(by_job.len(), 3);
+
+        let failed_backups = HistoryFilter { kind: Some("backup".to_string()), success: Some(false), ..Default::default() }.apply(sample());
+        assert_eq!(jobs(&failed_backups), ["a", "b"]);
+
+        let restored = HistoryFilter { job: Some("a".to_string()), kind: Some("restore".to_string()), success: Some(true), limit: None }.apply(sample());
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].report.snapshot_ids, ["a-snap"]);
+
+        assert!(HistoryFilter { job: Some("A".to_string()), ..Default::default() }.apply(sample()).is_empty());
+    }
+
+    #[test]
+    fn skips_unparsable_lines() {
+        let lines: Vec<String> = sample().iter().map(|e| serde_json::to_string(e).unwrap()).collect();
+        let content = format!("{}\n不是 JSON\n{{\"kind\":\"backup\"}}\n\n{}\n", lines[0], lines[1]);
+        let entries = parse_entries(&content);
+        assert_eq!(jobs(&entries), ["a", "b"]);
+        assert_eq!(entries[1].report.error_kind, Some(crate::error::ErrorKind::Other));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::config::FinalConfig;
+use crate::progress::JobOutput;
+use crate::report::JobResult;
+use console::style;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 默认的钩子超时时间 (秒)
+pub const DEFAULT_HOOK_TIMEOUT: u64 = 600;
+
+/// 摘要中保留的钩子输出行数
+const OUTPUT_TAIL_LINES: usize = 3;
+
+/// 钩子结束后，最多再等待多久让读取线程收完剩余的输出
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 通过系统 shell 执行一个钩子命令
+///
+/// `env` 中的变量会追加到子进程环境中；`timeout` 为 `None` 时不限制运行时间，超时后强制结束进程。
+/// 成功时返回用于任务摘要的一行描述，失败时返回同样带有输出摘要的错误信息。
+pub fn run_hook(name: &str, command: &str, env: &[(&str, String)], timeout: Option<Duration>, output: &JobOutput) -> Result<String, String> {
+    output.println(format!("{} 正在执行 {}: {}", style("i").blue(), name, style(command).dim()));
+
+    let mut shell = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    };
+    // 钩子在单独的进程组中运行，超时时连同它启动的子进程一起结束
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+    let mut child = shell
+        .envs(env.iter().map(|(k, v)| (*k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{} 启动失败: {}", name, e))?;
+
+    // 输出在线程中读取，避免管道写满导致子进程阻塞
+    let stdout_pipe = child.stdout.take().ok_or("无法读取钩子命令的标准输出")?;
+    let stderr_pipe = child.stderr.take().ok_or("无法读取钩子命令的标准错误输出")?;
+    let (stdout, stdout_reader) = spawn_reader(stdout_pipe);
+    let (stderr, stderr_reader) = spawn_reader(stderr_pipe);
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| format!("等待 {} 结束失败: {}", name, e))? {
+            Some(status) => break Some(status),
+            None if timeout.is_some_and(|t| started.elapsed() >= t) => {
+                kill_process_tree(&mut child);
+                break None;
+            }
+            None => thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    let elapsed = started.elapsed().as_secs_f64();
+
+    // 钩子留在后台的进程 (或脱离了进程组的子进程) 可能仍持有管道，只等待有限的时间，保留已经读到的部分输出
+    let drain_deadline = Instant::now() + OUTPUT_DRAIN_TIMEOUT;
+    for reader in [stdout_reader, stderr_reader] {
+        while !reader.is_finished() && Instant::now() < drain_deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        if reader.is_finished() {
+            let _ = reader.join();
+        }
+    }
+    // 钩子输出不一定是合法的 UTF-8 (例如被截断的多字节字符)，按字节读取后再转换
+    let captured = format!(
+        "{}{}",
+        String::from_utf8_lossy(&stdout.lock().unwrap()),
+        String::from_utf8_lossy(&stderr.lock().unwrap())
+    );
+    for line in captured.lines() {
+        output.println(format!("    {}", style(line).dim()));
+    }
+    let tail = output_tail(&captured);
+
+    match status {
+        Some(status) if status.success() => Ok(format!("{} 成功 (耗时 {:.1}s){}", name, elapsed, tail)),
+        Some(status) => Err(format!("{} 失败 ({}，耗时 {:.1}s){}", name, exit_description(status.code()), elapsed, tail)),
+        None => Err(format!("{} 超时 (超过 {}s)，已强制结束{}", name, timeout.unwrap_or_default().as_secs(), tail)),
+    }
+}
+
+/// 在线程中持续读取管道，读到的内容随时追加到共享缓冲区中
+fn spawn_reader(mut pipe: impl Read + Send + 'static) -> (Arc<Mutex<Vec<u8>>>, JoinHandle<()>) {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let shared = Arc::clone(&buffer);
+    let handle = thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            shared.lock().unwrap().extend_from_slice(&chunk[..n]);
+        }
+    });
+    (buffer, handle)
+}
+
+/// 强制结束钩子及其启动的全部子进程
+fn kill_process_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    let mut kill = if cfg!(windows) {
+        let mut c = Command::new("taskkill");
+        c.args(["/T", "/F", "/PID", &pid]);
+        c
+    } else {
+        let mut c = Command::new("kill");
+        c.args(["-KILL", "--", &format!("-{}", pid)]);
+        c
+    };
+    let _ = kill.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// 将钩子超时配置转换为 `Duration`，0 表示不限制
+pub fn timeout_from_secs(secs: Option<u64>) -> Option<Duration> {
+    match secs.unwrap_or(DEFAULT_HOOK_TIMEOUT) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+fn exit_description(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("退出码 {}", code),
+        None => "被信号终止".to_string(),
+    }
+}
+
+/// 取输出的最后几行，拼接为摘要中的附加信息
+fn output_tail(captured: &str) -> String {
+    let lines: Vec<&str> = captured.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let tail = &lines[lines.len().saturating_sub(OUTPUT_TAIL_LINES)..];
+    format!("，输出: {}", tail.join(" | "))
+}
+
+/// 钩子命令可用的环境变量
+///
+/// `result` 为 `None` 表示任务尚未执行 (前置命令)。
+pub fn job_env(config: &FinalConfig, repo_path: &Path, hook: &str, result: Option<&JobResult>) -> Vec<(&'static str, String)> {
+    let (status, snapshot_ids, error) = match result {
+        None => ("running", String::new(), String::new()),
+        Some(Ok((_, ids))) => ("success", ids.join(","), String::new()),
+        Some(Err(e)) => ("failure", String::new(), e.to_string()),
+    };
+    vec![
+        ("XCOMPRESS_HOOK", hook.to_string()),
+        ("XCOMPRESS_JOB", config.key_name.clone()),
+        ("XCOMPRESS_JOB_NAME", config.name.clone()),
+        ("XCOMPRESS_REPO", repo_path.to_string_lossy().into_owned()),
+        ("XCOMPRESS_PATHS", config.path.join("\n")),
+        ("XCOMPRESS_STATUS", status.to_string()),
+        ("XCOMPRESS_SNAPSHOT_IDS", snapshot_ids),
+        ("XCOMPRESS_ERROR", error),
+    ]
+}
@@ -0,0 +1,358 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
+use xcompress::backup::{self, DirectBackup};
+use xcompress::config::{self, ExcludeOptions};
+use xcompress::outln;
+use xcompress::progress::JobOutput;
+use xcompress::report::JobReport;
+use xcompress::utils::{self, is_restic_repo};
+use xcompress::{Compression, Error, InitOptions, PasswordSource, SourceRepo};
+
+/// 备份入口
+///
+/// 返回每个任务的执行结果；`Err` 仅表示配置文件或参数本身有误。
+/// `jobs` 为命令行 `--jobs` 指定的并行任务数，优先于配置文件中的 `max_parallel`。
+pub fn handle_backup(restic_exe_path: &str, config_path: Option<String>, target_path: Option<String>, jobs: Option<usize>) -> Result<Vec<JobReport>, Error> {
+    outln!("\n{}\n", style("--- 开始备份流程 ---").bold().yellow());
+
+    if let Some(path) = config_path {
+        // 模式一：使用指定的 toml 配置文件
+        run_toml(restic_exe_path, &path, jobs)
+    } else if let Some(path) = target_path {
+        // 模式二：直接备份指定的路径 (交互式)
+        if !Path::new(&path).exists() {
+            return Err(format!("错误: 提供的路径 '{}' 不存在。", path).into());
+        }
+        Ok(interactive_backup_report(restic_exe_path, Some(path))?.into_iter().collect())
+    } else {
+        // 模式三：无参数，检查默认 toml 或进入交互式
+        // 优先检查 backup_config.toml，其次检查 backup.toml
+        let default_tomls = vec!["backup_config.toml", "backup.toml"];
+        let mut found_toml = None;
+        for t in default_tomls {
+            if Path::new(t).exists() {
+                found_toml = Some(t);
+                break;
+            }
+        }
+        
+        if let Some(toml_file) = found_toml {
+            outln!("{} 检测到默认配置文件 '{}'，将使用该文件进行备份。", style("i").blue(), toml_file);
+            run_toml(restic_exe_path, toml_file, jobs)
+        } else {
+            outln!("{} 未提供参数且未找到默认配置文件，进入交互式备份模式。", style("i").blue());
+            Ok(interactive_backup_report(restic_exe_path, None)?.into_iter().collect())
+        }
+    }
+}
+
+/// 运行交互式备份并打印结果，用户取消时不生成报告也不记录历史
+fn interactive_backup_report(restic_exe_path: &str, target_path: Option<String>) -> Result<Option<JobReport>, Error> {
+    let Some(backup) = prompt_backup(target_path)? else {
+        return Ok(None);
+    };
+    let report = backup::run_direct_backup(restic_exe_path, &backup, &JobOutput::default());
+    print_result(&report);
+    Ok(Some(report))
+}
+
+pub fn handle_batch_backup(restic_exe_path: &str, jobs: Option<usize>) -> Result<Vec<JobReport>, Error> {
+    outln!("\n{}\n", style("--- 开始批量备份流程 ---").bold().yellow());
+    let theme = ColorfulTheme::default();
+
+    // 智能设置默认值
+    let default_val = if Path::new("backup_config.toml").exists() {
+        "backup_config.toml"
+    } else if Path::new("backup.toml").exists() {
+        "backup.toml"
+    } else {
+        "backup_config.toml"
+    };
+
+    // 1. Get TOML config path
+    let config_path: String = Input::with_theme(&theme)
+        .with_prompt("请输入或拖入 backup_config.toml 文件路径")
+        .default(default_val.into())
+        .validate_with(|input: &String| -> Result<(), &str> {
+            if Path::new(input).exists() { Ok(()) } else { Err("文件不存在，请重新输入。") }
+        })
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    
+    // 2. Parse TOML and run backups
+    run_toml(restic_exe_path, &config_path, jobs)
+}
+
+
+
+/// 交互式询问备份源、仓库与备份选项，用户取消时返回 `Ok(None)`
+fn prompt_backup(target_path: Option<String>) -> Result<Option<DirectBackup>, Error> {
+    let theme = ColorfulTheme::default();
+
+    // 1. Get path to back up
+    let backup_path_str = match &target_path {
+        Some(path) => path.clone(),
+        None => Input::with_theme(&theme)
+            .with_prompt("请输入或拖入要备份的文件/目录路径")
+            .validate_with(|input: &String| -> Result<(), &str> {
+                if Path::new(input).exists() { Ok(()) } else { Err("路径不存在，请重新输入。") }
+            })
+            .interact_text().map_err(|e| e.to_string())?,
+    };
+    let backup_path = Path::new(&backup_path_str);
+
+    // --- 智能仓库路径选择 logic ---
+    let mut repo_path_option: Option<PathBuf> = None;
+
+    // 如果是通过拖拽/命令行直接传入路径，进入“快速模式”
+    if target_path.is_some() {
+        let parent_dir = backup_path.parent().unwrap_or(Path::new("."));
+        let dir_name = backup_path.file_name().unwrap_or_default().to_string_lossy();
+        // 默认推荐: 在源目录旁边创建一个 xxx_repo 的目录
+        let suggested_repo_name = format!("{}_repo", dir_name);
+        let suggested_repo_path = parent_dir.join(&suggested_repo_name);
+
+        outln!("\n{}", style("--- 快速备份模式 ---").cyan().bold());
+        
+        let opts = vec![
+            format!("使用默认仓库位置: {} (推荐)", style(suggested_repo_path.display()).green()),
+            "自定义位置 / 选择现有仓库".to_string()
+        ];
+
+        let sel = Select::with_theme(&theme)
+            .with_prompt(format!("检测到源目录 '{}'，请选择备份方案", dir_name))
+            .items(&opts)
+            .default(0)
+            .interact()
+            .unwrap_or(0);
+
+        if sel == 0 {
+            repo_path_option = Some(suggested_repo_path);
+        }
+    }
+
+    // 新建仓库时的选项，只在选择「创建一个新的备份仓库」时询问
+    let mut init = InitOptions::default();
+
+    // 确定最终的 repo_path (解决编译器重复赋值/未初始化问题)
+    let repo_path = if let Some(path) = repo_path_option {
+        path
+    } else {
+        // --- 传统手动模式：选择 Base Dir -> 扫描/新建 ---
+        let exe_dir = env::current_dir().map_err(|e| format!("获取当前目录失败: {}", e))?;
+        let default_repo_base = exe_dir.to_string_lossy().to_string();
+        let repo_base_str: String = Input::with_theme(&theme)
+            .with_prompt("请输入 restic 仓库的存放目录 (留空则使用当前程序目录)")
+            .default(default_repo_base)
+            .interact_text()
+            .map_err(|e| e.to_string())?;
+        let repo_base_path = Path::new(&repo_base_str);
+
+        if !repo_base_path.exists() || !repo_base_path.is_dir() {
+            return Err(format!("仓库存放目录 '{}' 不是一个有效的目录。", repo_base_str).into());
+        }
+
+        outln!("\n{} 正在扫描 '{}' 下的 restic 仓库...", style("i").blue(), repo_base_path.display());
+        let mut existing_repos = Vec::new();
+        if let Ok(entries) = fs::read_dir(repo_base_path) {
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                if path.is_dir() && is_restic_repo(&path) {
+                    existing_repos.push(path);
+                }
+            }
+        }
+
+        let mut selection_items: Vec<String> = existing_repos
+            .iter()
+            .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
+            .collect();
+
+        selection_items.sort();
+        let create_new_option = "[ 创建一个新的备份仓库 ]".to_string();
+        selection_items.push(create_new_option.clone());
+
+        let selection = Select::with_theme(&theme)
+            .with_prompt("请选择要使用的备份仓库")
+            .items(&selection_items)
+            .default(0)
+            .interact()
+            .map_err(|e| e.to_string())?;
+
+        if selection_items[selection] == create_new_option {
+            let new_repo_path = loop {
+                let backup_name: String = Input::with_theme(&theme)
+                    .with_prompt("请输入新备份仓库的名称")
+                    .interact_text().map_err(|e| e.to_string())?;
+                let potential_path = repo_base_path.join(&backup_name);
+                if potential_path.exists() {
+                    eprintln!("{}", style(format!("错误: 目录 '{}' 已存在。", potential_path.display())).red());
+                } else {
+                    break potential_path;
+                }
+            };
+            init = prompt_init_options(&theme)?;
+            new_repo_path
+        } else {
+            repo_base_path.join(&selection_items[selection])
+        }
+    };
+
+    // 6. Get password and confirm
+    let password = Password::with_theme(&theme)
+        .with_prompt(format!("请输入仓库 '{}' 的密码", repo_path.file_name().unwrap_or_default().to_string_lossy()))
+        .with_confirmation("请再次输入密码确认", "两次输入的密码不匹配。")
+        .interact().map_err(|e| e.to_string())?;
+
+    // 7. 每次备份前询问 Pack Size (默认值修改为 128)
+    let size_opts = vec![
+        "最大 (128 MiB) - 文件少易上传网盘 / 压缩率最高 / 推荐 TB 级数据",
+        "默认 (16 MiB) - 文件碎片较多 / 本地性能最高 / 压缩率中等",
+        "自定义大小 (16-128 MiB)"
+    ];
+    let size_selection = Select::with_theme(&theme)
+        .with_prompt("请选择本次备份的存储单元大小 (Pack Size)")
+        .items(&size_opts)
+        .default(0)
+        .interact()
+        .map_err(|e| e.to_string())?;
+
+    let pack_size = match size_selection {
+        0 => 128,
+        1 => 16,
+        2 => Input::with_theme(&theme)
+            .with_prompt("请输入 Pack Size (MiB)")
+            .default(128)
+            .validate_with(|input: &u64| -> Result<(), &str> {
+                if *input >= 16 && *input <= 128 { Ok(()) } else { Err("范围必须在 16 到 128 之间") }
+            })
+            .interact_text()
+            .map_err(|e| e.to_string())?,
+        _ => 128,
+    };
+
+    // 8. 压缩模式 (格式 v1 的新仓库不询问)
+    let compression = if init.repo_version.as_deref() != Some("1") {
+        let compression_opts = [
+            "auto - 自动 (默认) / 兼顾速度与压缩率",
+            "max - 压缩率最高 / 速度较慢 / 适合上传网盘或冷备份",
+            "off - 不压缩 / 适合已压缩的视频、图片与归档",
+        ];
+        let selection = Select::with_theme(&theme)
+            .with_prompt("请选择本次备份的压缩模式")
+            .items(compression_opts)
+            .default(0)
+            .interact()
+            .map_err(|e| e.to_string())?;
+        Some(Compression::ALL[selection])
+    } else {
+        None
+    };
+
+    // 9. 排除规则 (可选)
+    let exclude_input: String = Input::with_theme(&theme)
+        .with_prompt("请输入要排除的模式，多个用逗号分隔 (例如 node_modules,target,*.tmp；留空不排除)")
+        .allow_empty(true)
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    let exclude_patterns: Vec<String> = exclude_input
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let exclude_caches = Confirm::with_theme(&theme)
+        .with_prompt("是否排除缓存目录 (包含 CACHEDIR.TAG 的目录)?")
+        .default(true)
+        .interact()
+        .map_err(|e| e.to_string())?;
+    let exclude = ExcludeOptions {
+        exclude: if exclude_patterns.is_empty() { None } else { Some(exclude_patterns) },
+        exclude_caches: Some(exclude_caches),
+        ..Default::default()
+    };
+
+    outln!("\n{} 准备将 '{}' 备份到 '{}'...", style("i").blue(), backup_path.display(), repo_path.display());
+
+    if !Confirm::with_theme(&theme).with_prompt("确认开始备份吗?").interact().unwrap_or(false) {
+        outln!("{}", style("操作已取消。").yellow());
+        return Ok(None);
+    }
+
+    Ok(Some(DirectBackup {
+        job_name: "interactive".to_string(),
+        paths: vec![backup_path_str],
+        repo: repo_path.to_string_lossy().into_owned(),
+        passwd: PasswordSource::Plain(password),
+        tag: String::new(),
+        pack_size,
+        compression,
+        exclude,
+        init,
+        unlock: super::ask_unlock(),
+    }))
+}
+
+/// 询问新建仓库的格式版本，以及是否从已有仓库复制分块参数
+fn prompt_init_options(theme: &ColorfulTheme) -> Result<InitOptions, Error> {
+    let version_opts = [
+        "2 - 支持压缩 (推荐，需要 restic 0.14 及以上版本读取)",
+        "1 - 不支持压缩 (兼容旧版 restic)",
+    ];
+    let version_selection = Select::with_theme(theme)
+        .with_prompt("请选择新仓库的格式版本")
+        .items(version_opts)
+        .default(0)
+        .interact()
+        .map_err(|e| e.to_string())?;
+    let repo_version = if version_selection == 1 { "1" } else { "2" };
+
+    let copy_chunker = Confirm::with_theme(theme)
+        .with_prompt("是否从已有仓库复制分块参数? (之后用 restic copy 在两个仓库之间复制快照时可以复用数据块)")
+        .default(false)
+        .interact()
+        .map_err(|e| e.to_string())?;
+    let copy_chunker_params_from = if copy_chunker {
+        let repo: String = Input::with_theme(theme)
+            .with_prompt("请输入或拖入来源仓库路径 (也可以是远程 URL)")
+            .validate_with(|input: &String| -> Result<(), &str> {
+                if utils::is_repo_location(input.trim()) { Ok(()) } else { Err("不是有效的 restic 仓库，请重新输入。") }
+            })
+            .interact_text()
+            .map_err(|e| e.to_string())?;
+        let password = Password::with_theme(theme)
+            .with_prompt("请输入来源仓库的密码")
+            .interact()
+            .map_err(|e| e.to_string())?;
+        Some(SourceRepo { repo: repo.trim().to_string(), password: PasswordSource::Plain(password) })
+    } else {
+        None
+    };
+
+    Ok(InitOptions { repo_version: Some(repo_version.to_string()), copy_chunker_params_from })
+}
+
+/// 解析备份配置文件并执行其中的任务，`jobs` 优先于配置文件中的 `max_parallel`
+fn run_toml(restic_exe_path: &str, config_path: &str, jobs: Option<usize>) -> Result<Vec<JobReport>, Error> {
+    let plan = config::parse_toml(config_path)?;
+    outln!("{} 成功解析配置文件，共找到 {} 个备份任务。", style("✔").green(), plan.jobs.len());
+    Ok(backup::run_toml_backup(restic_exe_path, plan.jobs, jobs.unwrap_or(plan.max_parallel), &JobOutput::default()))
+}
+
+/// 命令行非交互备份: 打印标题，将每个路径单独备份到指定的仓库并打印结果
+pub fn run_direct_backup(restic_exe_path: &str, backup: &DirectBackup) -> JobReport {
+    outln!("\n{}\n", style("--- 开始备份流程 ---").bold().yellow());
+    let report = backup::run_direct_backup(restic_exe_path, backup, &JobOutput::default());
+    print_result(&report);
+    report
+}
+
+fn print_result(report: &JobReport) {
+    if report.success {
+        outln!("{}\n{}", style("✔ 备份成功!").green().bold(), report.message);
+    } else {
+        crate::terminal::print_report_error("✖ 备份失败:", report);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use xcompress::browse::{parent_dir, Node, SnapshotBrowser};
+use xcompress::outln;
+use xcompress::utils::format_bytes;
+use xcompress::{Error, PasswordSource};
+
+fn node_label(node: &Node, selected: &BTreeSet<String>) -> String {
+    let mark = if selected.contains(&node.path) { style("✔").green().to_string() } else { " ".to_string() };
+    if node.is_dir() {
+        format!("{} 📁 {}/", mark, node.name)
+    } else {
+        format!("{} 📄 {}  {}", mark, node.name, style(format_bytes(node.size.unwrap_or(0))).dim())
+    }
+}
+
+/// 交互式浏览快照的文件树并勾选要恢复的文件/目录
+///
+/// 返回选中项在快照中的完整路径 (restic 格式)，用户取消时返回 `Ok(None)`。
+pub fn browse_snapshot(restic_exe_path: &str, repo_path: &str, password: &PasswordSource, snapshot_id: &str) -> Result<Option<Vec<String>>, Error> {
+    let theme = ColorfulTheme::default();
+    let mut browser = SnapshotBrowser::new(restic_exe_path, repo_path, password, snapshot_id);
+    let mut selected: BTreeSet<String> = BTreeSet::new();
+    let mut current_dir = "/".to_string();
+
+    loop {
+        let nodes = browser.list_dir(&current_dir)?.to_vec();
+
+        let mut items = vec![
+            format!("[ 完成选择并恢复 (已选 {} 项) ]", selected.len()),
+            "[ 在当前目录中勾选多个文件/目录... ]".to_string(),
+            "[ .. 返回上一级 ]".to_string(),
+        ];
+        items.extend(nodes.iter().map(|n| node_label(n, &selected)));
+
+        let selection = Select::with_theme(&theme)
+            .with_prompt(format!("当前位置: {} (进入目录 / 选中文件切换勾选，按 'q' 取消)", current_dir))
+            .items(&items)
+            .default(if nodes.is_empty() { 2 } else { 3 })
+            .max_length(20)
+            .interact_opt()
+            .map_err(|e| e.to_string())?;
+
+        match selection {
+            None => return Ok(None),
+            Some(0) => {
+                if selected.is_empty() {
+                    outln!("{}", style("尚未选择任何文件或目录。").yellow());
+                    continue;
+                }
+                return Ok(Some(selected.into_iter().collect()));
+            }
+            Some(1) => {
+                if nodes.is_empty() {
+                    continue;
+                }
+                let labels: Vec<String> = nodes.iter().map(|n| node_label(n, &BTreeSet::new())).collect();
+                let defaults: Vec<bool> = nodes.iter().map(|n| selected.contains(&n.path)).collect();
+                let picked = MultiSelect::with_theme(&theme)
+                    .with_prompt("空格勾选/取消，回车确认")
+                    .items(&labels)
+                    .defaults(&defaults)
+                    .max_length(20)
+                    .interact()
+                    .map_err(|e| e.to_string())?;
+                for node in &nodes {
+                    selected.remove(&node.path);
+                }
+                for i in picked {
+                    selected.insert(nodes[i].path.clone());
+                }
+            }
+            Some(2) => current_dir = parent_dir(&current_dir),
+            Some(i) => {
+                let node = &nodes[i - 3];
+                if node.is_dir() {
+                    current_dir = node.path.clone();
+                } else if !selected.remove(&node.path) {
+                    selected.insert(node.path.clone());
+                }
+            }
+        }
+    }
+}
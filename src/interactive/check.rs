@@ -0,0 +1,45 @@
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use xcompress::check::{is_valid_data_subset, run_check};
+use xcompress::outln;
+use xcompress::progress::JobOutput;
+use xcompress::Error;
+
+/// 交互式校验仓库 (主菜单入口)，成功时返回仓库路径与 restic 输出
+pub fn handle_check(restic_exe_path: &str) -> Result<(String, String), Error> {
+    outln!("\n{}\n", style("--- 开始校验仓库 ---").bold().yellow());
+    let theme = ColorfulTheme::default();
+    let (repo_path_str, password) = super::prompt_repo(&theme)?;
+
+    let modes = &[
+        "仅校验仓库结构 (快速)",
+        "同时读取部分数据进行校验 (--read-data-subset)",
+        "读取全部数据进行校验 (最慢，最可靠)",
+    ];
+    let mode = Select::with_theme(&theme)
+        .with_prompt("请选择校验方式")
+        .items(modes)
+        .default(0)
+        .interact()
+        .map_err(|e| e.to_string())?;
+
+    let subset = match mode {
+        1 => Some(
+            Input::with_theme(&theme)
+                .with_prompt("请输入要读取的数据比例 (例如 5%、1/10 或 2G)")
+                .default("5%".to_string())
+                .validate_with(|input: &String| -> Result<(), &str> {
+                    if is_valid_data_subset(input) { Ok(()) } else { Err("格式无效，示例: 5%、1/10、2G") }
+                })
+                .interact_text()
+                .map_err(|e| e.to_string())?,
+        ),
+        2 => Some("100%".to_string()),
+        _ => None,
+    };
+
+    let output = JobOutput::default();
+    let result = run_check(restic_exe_path, &repo_path_str, &password, subset.as_deref(), super::ask_unlock().suspending(&output), &output)?;
+    outln!("{}\n{}", style("✔ 仓库校验通过!").green().bold(), result);
+    Ok((repo_path_str, result))
+}
@@ -0,0 +1,93 @@
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use xcompress::diff::{group_lines, run_diff, SnapshotDiff};
+use xcompress::outln;
+use xcompress::progress::JobOutput;
+use xcompress::{restore, Error};
+
+/// 交互式查看时每组最多列出的路径数
+const LIST_LIMIT: usize = 50;
+
+/// 打印完整的差异分组
+fn print_diff(diff: &SnapshotDiff) {
+    let title = match &diff.path_filter {
+        Some(filter) => format!("--- 快照 {} → {} (筛选: {}) ---", diff.from, diff.to, filter),
+        None => format!("--- 快照 {} → {} ---", diff.from, diff.to),
+    };
+    outln!("\n{}", style(title).cyan().bold());
+    if diff.added.is_empty() && diff.removed.is_empty() && diff.modified.is_empty() {
+        outln!("  {}", style("没有差异。").dim());
+    }
+    for line in group_lines(diff, LIST_LIMIT) {
+        outln!("{}", line);
+    }
+    outln!("\n{} {}", style("i").blue(), diff.describe());
+}
+
+/// 交互式比较两个快照 (主菜单入口)
+pub fn handle_diff(restic_exe_path: &str) -> Result<(), Error> {
+    outln!("\n{}\n", style("--- 比较快照 ---").bold().yellow());
+    let theme = ColorfulTheme::default();
+    let (repo_path_str, password) = super::prompt_repo(&theme)?;
+
+    outln!("\n{} 正在获取快照列表...", style("i").blue());
+    let snapshots = restore::get_snapshots(restic_exe_path, &repo_path_str, &password)?;
+    if snapshots.len() < 2 {
+        return Err("仓库中至少需要两个快照才能比较。".into());
+    }
+
+    // 快照按时间从新到旧排列，默认比较最新的两个
+    let items: Vec<String> = snapshots.iter().map(super::snapshot_label).collect();
+    let Some(from) = Select::with_theme(&theme)
+        .with_prompt("请选择较旧的快照 (比较起点，按 'q' 退出)")
+        .items(&items)
+        .default(1)
+        .interact_opt()
+        .map_err(|e| e.to_string())?
+    else {
+        outln!("{}", style("操作已取消。").yellow());
+        return Ok(());
+    };
+    let Some(to) = Select::with_theme(&theme)
+        .with_prompt("请选择较新的快照 (比较终点，按 'q' 退出)")
+        .items(&items)
+        .default(if from == 0 { 1 } else { 0 })
+        .interact_opt()
+        .map_err(|e| e.to_string())?
+    else {
+        outln!("{}", style("操作已取消。").yellow());
+        return Ok(());
+    };
+    if from == to {
+        return Err("请选择两个不同的快照。".into());
+    }
+
+    outln!("\n{} 正在比较快照 {} 与 {} ...", style("i").blue(), snapshots[from].short_id, snapshots[to].short_id);
+    let diff = run_diff(restic_exe_path, &repo_path_str, &password, &snapshots[from].short_id, &snapshots[to].short_id, &JobOutput::default())?;
+    print_diff(&diff);
+
+    // 差异只计算一次，之后可以反复按路径筛选
+    loop {
+        let filter: String = Input::with_theme(&theme)
+            .with_prompt("输入路径进行筛选 (以 / 开头按前缀匹配，否则按包含匹配；留空返回)")
+            .allow_empty(true)
+            .interact_text()
+            .map_err(|e| e.to_string())?;
+        if filter.trim().is_empty() {
+            return Ok(());
+        }
+        print_diff(&diff.filtered(&filter));
+    }
+}
+
+/// 将差异以 JSON 写入文件，未指定文件时输出到标准输出 (命令行 `diff` 子命令)
+pub fn emit_json(diff: &SnapshotDiff, output_file: Option<&str>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(diff).map_err(|e| format!("生成差异 JSON 失败: {}", e))?;
+    match output_file {
+        Some(path) => std::fs::write(path, json + "\n").map_err(|e| format!("写入差异文件 '{}' 失败: {}", path, e)),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
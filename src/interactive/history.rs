@@ -0,0 +1,144 @@
+use chrono::DateTime;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use xcompress::history::{history_path, query, HistoryEntry, HistoryFilter};
+use xcompress::outln;
+use xcompress::utils::format_bytes;
+
+fn format_time(rfc3339: &str) -> String {
+    DateTime::parse_from_rfc3339(rfc3339)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|_| rfc3339.to_string())
+}
+
+fn kind_label(kind: &str) -> &str {
+    match kind {
+        "backup" => "备份",
+        "restore" => "恢复",
+        other => other,
+    }
+}
+
+/// 单行摘要，用于列表显示
+fn summary_line(entry: &HistoryEntry) -> String {
+    let report = &entry.report;
+    let mark = if report.success { style("✔").green() } else { style("✖").red() };
+    let detail = if report.success {
+        let mut parts = Vec::new();
+        if !report.snapshot_ids.is_empty() {
+            parts.push(format!("快照 {}", report.snapshot_ids.join(",")));
+        }
+        if let Some(bytes) = report.bytes_added {
+            parts.push(format!("新增 {}", format_bytes(bytes)));
+        }
+        parts.join("  ")
+    } else {
+        report.error.as_deref().unwrap_or_default().lines().next().unwrap_or_default().to_string()
+    };
+    format!(
+        "{}  {}  {}  {:<16}  {:>7.1}s  {}",
+        format_time(&report.started_at),
+        mark,
+        kind_label(&entry.kind),
+        report.job,
+        report.duration_secs,
+        style(detail).dim()
+    )
+}
+
+/// 打印一条记录的全部信息
+fn print_details(entry: &HistoryEntry) {
+    let report = &entry.report;
+    let status = if report.success { style("成功").green() } else { style("失败").red() };
+    outln!("\n{}", style(format!("--- {} 任务: {} ---", kind_label(&entry.kind), report.job)).cyan().bold());
+    outln!("  状态: {}", status);
+    outln!("  仓库: {}", report.repo);
+    if !report.sources.is_empty() {
+        outln!("  备份源:");
+        for source in &report.sources {
+            outln!("    - {}", source);
+        }
+    }
+    outln!("  开始: {}", format_time(&report.started_at));
+    outln!("  结束: {}", format_time(&report.finished_at));
+    outln!("  耗时: {:.1}s", report.duration_secs);
+    if !report.snapshot_ids.is_empty() {
+        outln!("  快照: {}", report.snapshot_ids.join(", "));
+    }
+    if let Some(bytes) = report.bytes_added {
+        outln!("  新增数据: {}", format_bytes(bytes));
+    }
+    if !report.message.is_empty() {
+        outln!("  信息: {}", report.message);
+    }
+    if let Some(error) = &report.error {
+        outln!("  错误: {}", style(error).red());
+    }
+}
+
+/// `history` 子命令: 按条件列出历史记录，`details` 为真时打印每条记录的全部信息
+pub fn handle_history(filter: &HistoryFilter, details: bool) -> Result<(), String> {
+    let entries = query(filter)?;
+    if entries.is_empty() {
+        outln!("{} 没有符合条件的运行记录 (历史文件: {})。", style("i").blue(), history_path().display());
+        return Ok(());
+    }
+    outln!("{} 共 {} 条记录 (最新的在前):\n", style("i").blue(), entries.len());
+    for entry in &entries {
+        if details {
+            print_details(entry);
+        } else {
+            outln!("{}", summary_line(entry));
+        }
+    }
+    Ok(())
+}
+
+/// 交互式查看运行历史 (主菜单入口)
+pub fn handle_history_menu() -> Result<(), String> {
+    let theme = ColorfulTheme::default();
+    let modes = &["最近的全部记录", "按任务键名筛选", "仅显示失败的记录"];
+    let mode = Select::with_theme(&theme)
+        .with_prompt("请选择要查看的运行记录")
+        .items(modes)
+        .default(0)
+        .interact_opt()
+        .map_err(|e| e.to_string())?;
+
+    let filter = match mode {
+        None => return Ok(()),
+        Some(1) => HistoryFilter {
+            job: Some(
+                Input::with_theme(&theme)
+                    .with_prompt("请输入任务键名 (例如 project_A)")
+                    .interact_text()
+                    .map_err(|e| e.to_string())?,
+            ),
+            limit: Some(50),
+            ..Default::default()
+        },
+        Some(2) => HistoryFilter { success: Some(false), limit: Some(50), ..Default::default() },
+        Some(_) => HistoryFilter { limit: Some(50), ..Default::default() },
+    };
+
+    let entries = query(&filter)?;
+    if entries.is_empty() {
+        outln!("{} 没有符合条件的运行记录。", style("i").blue());
+        return Ok(());
+    }
+
+    let items: Vec<String> = entries.iter().map(summary_line).collect();
+    loop {
+        let selection = Select::with_theme(&theme)
+            .with_prompt("选择一条记录查看详情 (按 'q' 返回)")
+            .items(&items)
+            .default(0)
+            .max_length(20)
+            .interact_opt()
+            .map_err(|e| e.to_string())?;
+        match selection {
+            Some(i) => print_details(&entries[i]),
+            None => return Ok(()),
+        }
+    }
+}
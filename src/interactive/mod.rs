@@ -0,0 +1,56 @@
+pub mod backup;
+pub mod browse;
+pub mod check;
+pub mod diff;
+pub mod history;
+pub mod restore;
+
+use std::sync::Arc;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password};
+use xcompress::lock::UnlockPolicy;
+use xcompress::outln;
+use xcompress::{utils, Error, LockInfo, PasswordSource, Snapshot};
+
+/// 选择列表中显示的快照单行描述
+pub fn snapshot_label(snapshot: &Snapshot) -> String {
+    format!(
+        "{}  ({})  {}  [{}]",
+        snapshot.short_id,
+        snapshot.time.split('T').next().unwrap_or(""),
+        style(utils::format_bytes(snapshot.size)).dim(),
+        snapshot.paths.join(", ")
+    )
+}
+
+/// 列出持有锁的进程并询问用户是否移除，进度条显示期间使用时配合 `UnlockPolicy::suspending`
+pub fn ask_unlock() -> UnlockPolicy {
+    UnlockPolicy::Decide(Arc::new(|locks: &[LockInfo]| {
+        outln!("{} 仓库被以下进程锁定:", style("!").yellow());
+        for lock in locks {
+            outln!("  - {}", lock.describe());
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("确认以上进程都已不再运行，移除这些锁并重试吗?")
+            .default(false)
+            .interact()
+            .unwrap_or(false)
+    }))
+}
+
+/// 询问仓库路径 (本地目录或远程 URL) 与密码
+fn prompt_repo(theme: &ColorfulTheme) -> Result<(String, PasswordSource), Error> {
+    let repo: String = Input::with_theme(theme)
+        .with_prompt("请输入或拖入 restic 仓库路径")
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    let repo = repo.trim().to_string();
+    if !utils::is_repo_location(&repo) {
+        return Err(Error::RepoNotFound(format!("{} 不是有效的 restic 仓库", repo)));
+    }
+    let password = Password::with_theme(theme)
+        .with_prompt("请输入仓库密码")
+        .interact()
+        .map_err(|e| e.to_string())?;
+    Ok((repo, PasswordSource::Plain(password)))
+}
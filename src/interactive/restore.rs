@@ -0,0 +1,267 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
+use xcompress::archive::{self, ArchiveFormat};
+use xcompress::config::{self, PasswordSource};
+use xcompress::outln;
+use xcompress::progress::{with_spinner, JobOutput};
+use xcompress::report::JobReport;
+use xcompress::{restore, utils, Error, ResticClient, RestoreRequest, Snapshot};
+
+/// 交互式恢复，返回 `Ok(false)` 表示用户取消
+pub fn handle_restore(restic_exe_path: &str, repo_path_arg: Option<String>, passwd_arg: Option<PasswordSource>) -> Result<bool, Error> {
+    outln!("\n{}\n", style("--- 开始恢复流程 ---").bold().yellow());
+    
+    let theme = ColorfulTheme::default();
+    
+    // 如果命令行已提供路径，则使用它，否则提示用户输入
+    let repo_path_str: String = match repo_path_arg {
+        Some(path) => {
+            outln!("{} 使用命令行提供的仓库路径: {}", style("✔").green(), style(&path).dim());
+            path
+        }
+        None => Input::with_theme(&theme)
+            .with_prompt("请输入或拖入 restic 仓库路径")
+            .interact_text()
+            .map_err(|e| e.to_string())?,
+    };
+    
+    let repo_path = Path::new(repo_path_str.trim());
+    if !utils::is_repo_location(repo_path_str.trim()) {
+        return Err(Error::RepoNotFound(format!("{} 不是有效的 restic 仓库", repo_path.display())));
+    }
+
+    // 获取密码 (命令行已提供时不再提示)
+    let password = match passwd_arg {
+        Some(passwd) => passwd,
+        None => PasswordSource::Plain(
+            Password::with_theme(&theme)
+                .with_prompt("请输入仓库密码")
+                .interact()
+                .map_err(|e| e.to_string())?,
+        ),
+    };
+
+    // 获取快照列表
+    outln!("\n{} 正在获取快照列表...", style("i").blue());
+    let snapshots = restore::get_snapshots(restic_exe_path, &repo_path.to_string_lossy(), &password)?;
+    
+    if snapshots.is_empty() {
+        return Err("仓库中未找到任何快照。".into());
+    }
+
+    // 1. 让用户选择快照 (显示更详细信息)
+    let snapshot_items: Vec<String> = snapshots.iter().map(super::snapshot_label).collect();
+    
+    let selection_idx = match Select::with_theme(&theme)
+        .with_prompt("请选择要恢复的快照 (按 'q' 退出)")
+        .items(&snapshot_items)
+        .default(0)
+        .interact_opt()
+        .map_err(|e| e.to_string())?
+    {
+        Some(index) => index,
+        None => {
+            outln!("{}", style("操作已取消。").yellow());
+            return Ok(false);
+        }
+    };
+    let selected_snapshot = &snapshots[selection_idx];
+
+    // 2. 让用户选择恢复模式
+    let restore_modes = &[
+        "仅恢复最后一级目录 (推荐, 类似解压)",
+        "按原始完整路径恢复 (restic 默认行为)",
+        "浏览快照内容，仅恢复选中的文件/目录",
+        "打包为单个归档文件 (zip / tar / tar.gz / tar.zst)",
+    ];
+    let mode_selection = Select::with_theme(&theme)
+        .with_prompt("请选择恢复模式")
+        .items(restore_modes)
+        .default(0)
+        .interact()
+        .map_err(|e| e.to_string())?;
+
+    if mode_selection == 3 {
+        return restore_to_archive(restic_exe_path, repo_path, &password, selected_snapshot);
+    }
+
+    // 3. 根据模式确定恢复范围
+    let mut path_to_restore: &str = "";
+    let mut include_paths: Vec<String> = Vec::new();
+    match mode_selection {
+        0 => {
+            // 如果快照有多个路径，让用户选择一个
+            path_to_restore = if selected_snapshot.paths.len() > 1 {
+                let path_selection = Select::with_theme(&theme)
+                    .with_prompt("此快照包含多个路径，请选择要恢复哪一个")
+                    .items(&selected_snapshot.paths)
+                    .default(0)
+                    .interact()
+                    .map_err(|e| e.to_string())?;
+                &selected_snapshot.paths[path_selection]
+            } else if let Some(path) = selected_snapshot.paths.first() {
+                path
+            } else {
+                return Err("此快照不包含任何可恢复的路径。".into());
+            };
+        }
+        2 => {
+            let repo_path_lossy = repo_path.to_string_lossy();
+            match super::browse::browse_snapshot(restic_exe_path, &repo_path_lossy, &password, &selected_snapshot.short_id)? {
+                Some(paths) => include_paths = paths,
+                None => {
+                    outln!("{}", style("操作已取消。").yellow());
+                    return Ok(false);
+                }
+            }
+            outln!("{} 已选择 {} 项，将按原始完整路径恢复到目标目录中:", style("i").blue(), include_paths.len());
+            for p in &include_paths {
+                outln!("  - {}", style(p).dim());
+            }
+        }
+        _ => {}
+    }
+
+    // 4. 决定输出路径
+    // 智能推断默认恢复路径: 优先使用本地仓库的上级目录 (模拟原地解压)，远程仓库使用当前目录
+    let local_parent = repo_path.parent().filter(|_| !utils::is_remote_repo(repo_path_str.trim()));
+    let default_output_path = if let Some(parent) = local_parent {
+        parent.to_string_lossy().to_string()
+    } else {
+        env::current_dir().map_err(|e| e.to_string())?.to_string_lossy().to_string()
+    };
+
+    let output_path_str: String = Input::with_theme(&theme)
+        .with_prompt("请输入恢复目标路径 (默认恢复到仓库同级目录)")
+        .default(default_output_path)
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    
+    outln!("\n{} 準備恢復快照 {} 到 '{}'...", style("i").blue(), selected_snapshot.short_id, output_path_str);
+    
+    // 5. 构建恢复参数
+    let snapshot_arg = match Path::new(path_to_restore).parent() {
+        // 模式: 剥离路径
+        Some(parent) if mode_selection == 0 => format!("{}:{}", selected_snapshot.short_id, utils::convert_to_restic_path(parent)),
+        // 模式: 完整路径 / 浏览选择
+        _ => selected_snapshot.short_id.clone(),
+    };
+    let request = RestoreRequest {
+        snapshot: snapshot_arg,
+        target: PathBuf::from(&output_path_str),
+        include: include_paths,
+    };
+    let output = JobOutput::default();
+    let client = ResticClient::new(restic_exe_path, repo_path.to_string_lossy(), password).with_unlock_policy(super::ask_unlock().suspending(&output));
+
+    match with_spinner(&output, || client.restore(&request)) {
+        Ok(output) => {
+            outln!("{}\n{}", style("✔ 恢复成功!").green().bold(), output);
+            Ok(true)
+        },
+        Err(e) => Err(e.with_message(|e| format!("恢复失败: {}", e))),
+    }
+}
+
+/// 交互式将快照 (或其中一个路径) 打包为归档文件
+fn restore_to_archive(restic_exe_path: &str, repo_path: &Path, password: &PasswordSource, snapshot: &Snapshot) -> Result<bool, Error> {
+    let theme = ColorfulTheme::default();
+
+    // 快照包含多个路径时可以只打包其中一个
+    let source = if snapshot.paths.len() > 1 {
+        let mut items = vec!["整个快照".to_string()];
+        items.extend(snapshot.paths.iter().cloned());
+        let selection = Select::with_theme(&theme)
+            .with_prompt("此快照包含多个路径，请选择要打包的内容")
+            .items(&items)
+            .default(0)
+            .interact()
+            .map_err(|e| e.to_string())?;
+        match selection {
+            0 => "/".to_string(),
+            i => utils::convert_to_restic_path(Path::new(&snapshot.paths[i - 1])),
+        }
+    } else {
+        "/".to_string()
+    };
+
+    let format_items: Vec<&str> = ArchiveFormat::ALL.iter().map(|f| f.extension()).collect();
+    let format = ArchiveFormat::ALL[Select::with_theme(&theme)
+        .with_prompt("请选择归档格式")
+        .items(&format_items)
+        .default(0)
+        .interact()
+        .map_err(|e| e.to_string())?];
+
+    // 默认写到仓库同级目录，文件名为 "仓库名_快照ID.扩展名"
+    let repo_name = repo_path.file_name().unwrap_or_default().to_string_lossy();
+    let default_dest = repo_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(format!("{}_{}.{}", repo_name, snapshot.short_id, format.extension()));
+    let dest: String = Input::with_theme(&theme)
+        .with_prompt("请输入归档文件的保存路径")
+        .default(default_dest.to_string_lossy().into_owned())
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    let dest = Path::new(dest.trim());
+    if dest.exists() && !Confirm::with_theme(&theme)
+        .with_prompt(format!("文件 '{}' 已存在，是否覆盖?", dest.display()))
+        .default(false)
+        .interact()
+        .map_err(|e| e.to_string())?
+    {
+        outln!("{}", style("操作已取消。").yellow());
+        return Ok(false);
+    }
+
+    outln!("\n{} 正在将快照 {} 打包到 '{}'...", style("i").blue(), snapshot.short_id, dest.display());
+    let size = archive::dump_to_archive(restic_exe_path, &repo_path.to_string_lossy(), password, &snapshot.short_id, &source, format, dest)
+        .map_err(|e| e.with_message(|e| format!("打包失败: {}", e)))?;
+    outln!("{} 已生成归档 {} ({})", style("✔ 打包成功!").green().bold(), dest.display(), utils::format_bytes(size));
+    Ok(true)
+}
+
+pub fn handle_batch_restore(restic_exe_path: &str, config_path_arg: Option<String>) -> Result<Vec<JobReport>, Error> {
+    outln!("\n{}\n", style("--- 开始批量恢复流程 ---").bold().yellow());
+    let theme = ColorfulTheme::default();
+
+    let config_path: String = match config_path_arg {
+        Some(path) => path,
+        None => Input::with_theme(&theme)
+            .with_prompt("请输入或拖入 restore_config.toml 文件路径")
+            .default("restore_config.toml".into())
+            .validate_with(|input: &String| -> Result<(), &str> {
+                if Path::new(input).exists() { Ok(()) } else { Err("文件不存在，请重新输入。") }
+            })
+            .interact_text()
+            .map_err(|e| e.to_string())?,
+    };
+
+    let configs = config::parse_restore_toml(&config_path)?;
+
+    outln!("{} 成功解析恢复配置文件，共找到 {} 个恢复任务。", style("✔").green(), configs.len());
+    Ok(restore::run_restore_jobs(restic_exe_path, configs, &JobOutput::default()))
+}
+
+/// 非交互地列出仓库中的快照 (命令行 `snapshots` 子命令)
+pub fn list_snapshots(restic_exe_path: &str, repo_path: &str, password: &PasswordSource) -> Result<(), Error> {
+    let snapshots = restore::get_snapshots(restic_exe_path, repo_path, password)?;
+    if snapshots.is_empty() {
+        outln!("{}", style("仓库中未找到任何快照。").yellow());
+        return Ok(());
+    }
+    for s in &snapshots {
+        outln!(
+            "{}  {}  {:>12}  [{}]",
+            style(&s.short_id).cyan(),
+            s.time.split('.').next().unwrap_or(""),
+            utils::format_bytes(s.size),
+            s.paths.join(", ")
+        );
+    }
+    outln!("\n{} 共 {} 个快照。", style("i").blue(), snapshots.len());
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! xcompress 的核心逻辑: restic 客户端、配置解析以及备份/恢复流程
+//!
+//! 其它程序可以直接使用 [`ResticClient`] 操作仓库: 它不打印任何内容，备份进度通过回调报告，
+//! 失败时返回可匹配的 [`Error`]。
+//! 命令行解析、帮助信息、交互式提示以及错误在终端上的显示只属于 `xcompress` 可执行文件，不在库中:
+//! 库中的流程不会等待用户输入，只返回结果或错误；执行过程中的文本都经由调用方传入的 [`progress::JobOutput`] 输出，
+//! 默认打印到终端，也可以用 `JobOutput::with_sink` 交给回调处理。
+
+pub mod error;
+pub mod utils;
+pub mod version;
+pub mod client;
+pub mod config;
+pub mod backup;
+pub mod restore;
+pub mod archive;
+pub mod browse;
+pub mod check;
+pub mod diff;
+pub mod forget;
+pub mod history;
+pub mod hooks;
+pub mod lock;
+pub mod progress;
+pub mod report;
+pub mod schedule;
+pub mod daemon;
+
+pub use client::{BackupRequest, Compression, InitOptions, LockInfo, ResticClient, RestoreRequest, Snapshot, SourceRepo};
+pub use config::PasswordSource;
+pub use error::{Error, ErrorKind, FieldError};
+pub use progress::{BackupEvent, BackupStatus, BackupSummary};
+pub use version::ResticVersion;
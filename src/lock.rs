@@ -0,0 +1,145 @@
+use crate::client::LockInfo;
+use crate::progress::JobOutput;
+use crate::utils::format_duration;
+use console::style;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 由调用方决定是否移除仓库锁 (例如询问用户)，返回 `true` 时移除这些锁并重试
+pub type UnlockDecision = Arc<dyn Fn(&[LockInfo]) -> bool + Send + Sync>;
+
+/// 操作因仓库被锁定而失败时的处理方式 (见 `ResticClient::with_unlock_policy`)
+#[derive(Clone, Default)]
+pub enum UnlockPolicy {
+    /// 不移除锁，错误信息中列出持有锁的进程
+    #[default]
+    Never,
+    /// 所有锁都超过该时长未刷新时自动移除并重试 (配置 `auto_unlock_stale_after`)
+    StaleAfter(Duration),
+    /// 交给调用方决定，交互式前端用它询问用户
+    Decide(UnlockDecision),
+}
+
+impl UnlockPolicy {
+    /// 是否移除 `locks` 并重试
+    pub fn should_unlock(&self, locks: &[LockInfo]) -> bool {
+        match self {
+            UnlockPolicy::Never => false,
+            UnlockPolicy::StaleAfter(limit) => all_stale(locks, *limit),
+            UnlockPolicy::Decide(decide) => decide(locks),
+        }
+    }
+
+    /// 决定期间暂时隐藏 `output` 中的进度条，交给调用方决定时 (例如询问用户) 才需要
+    pub fn suspending(self, output: &JobOutput) -> UnlockPolicy {
+        match self {
+            UnlockPolicy::Decide(decide) => {
+                let output = output.clone();
+                UnlockPolicy::Decide(Arc::new(move |locks: &[LockInfo]| output.suspend(|| decide(locks))))
+            }
+            other => other,
+        }
+    }
+
+    /// 在决定之前将持有锁的进程与处理结果打印到任务输出中 (用于批量任务)
+    pub fn reported(self, output: &JobOutput) -> UnlockPolicy {
+        let output = output.clone();
+        UnlockPolicy::Decide(Arc::new(move |locks: &[LockInfo]| {
+            output.println(format!("{} 仓库被以下进程锁定:", style("!").yellow()));
+            for lock in locks {
+                output.println(format!("  - {}", lock.describe()));
+            }
+            let unlock = self.should_unlock(locks);
+            match &self {
+                _ if unlock => output.println(format!("{} 正在移除 {} 个仓库锁并重试...", style("i").blue(), locks.len())),
+                UnlockPolicy::StaleAfter(limit) => output.println(format!(
+                    "{} 仍有锁在 {} 内刷新过 (auto_unlock_stale_after)，不自动移除。",
+                    style("i").blue(),
+                    format_duration(*limit)
+                )),
+                _ => {}
+            }
+            unlock
+        }))
+    }
+}
+
+impl fmt::Debug for UnlockPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnlockPolicy::Never => f.write_str("Never"),
+            UnlockPolicy::StaleAfter(limit) => f.debug_tuple("StaleAfter").field(limit).finish(),
+            UnlockPolicy::Decide(_) => f.write_str("Decide(..)"),
+        }
+    }
+}
+
+/// 所有锁是否都已超过 `limit` 未刷新，刷新时间无法解析的锁视为仍在使用
+pub fn all_stale(locks: &[LockInfo], limit: Duration) -> bool {
+    locks.iter().all(|lock| lock.age().is_some_and(|age| age >= limit))
+}
+
+/// 持有锁的主机与进程，用于错误信息
+pub fn holders(locks: &[LockInfo]) -> String {
+    locks
+        .iter()
+        .map(|lock| format!("{} PID {}", lock.hostname, lock.pid))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorKind};
+    use chrono::Local;
+
+    fn lock_refreshed(ago: chrono::Duration, pid: u64) -> LockInfo {
+        LockInfo {
+            id: "0123456789abcdef".to_string(),
+            time: (Local::now() - ago).to_rfc3339(),
+            hostname: "srv1".to_string(),
+            pid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_locked_repository_errors() {
+        let stderr = "repository 1234 opened\nFatal: unable to create lock in backend: repository is already locked by PID 4242 on srv1 by bob (UID 1000, GID 1000)\nlock was created at 2026-10-17 10:00:00 (3h0m0s ago)";
+        let err = Error::from_restic_output("", stderr);
+        assert_eq!(err.kind(), ErrorKind::RepoLocked);
+        assert!(err.to_string().contains("PID 4242"), "{}", err);
+
+        let err = Error::from_restic_output("", "Fatal: unable to open config file: Stat: stat /repo/config: no such file or directory");
+        assert_ne!(err.kind(), ErrorKind::RepoLocked);
+    }
+
+    #[test]
+    fn computes_lock_age() {
+        let age = lock_refreshed(chrono::Duration::hours(3), 1).age().unwrap();
+        assert!(age >= Duration::from_secs(3 * 3600) && age < Duration::from_secs(3 * 3600 + 60), "{:?}", age);
+        assert_eq!(LockInfo { time: "不是时间".to_string(), ..Default::default() }.age(), None);
+        assert_eq!(lock_refreshed(chrono::Duration::hours(3), 1).short_id(), "01234567");
+    }
+
+    #[test]
+    fn unlocks_only_when_every_lock_is_stale() {
+        let limit = Duration::from_secs(2 * 3600);
+        let stale = lock_refreshed(chrono::Duration::hours(3), 1);
+        let fresh = lock_refreshed(chrono::Duration::minutes(5), 2);
+        let unknown = LockInfo { time: "bad".to_string(), ..Default::default() };
+
+        assert!(all_stale(std::slice::from_ref(&stale), limit));
+        assert!(!all_stale(&[stale.clone(), fresh.clone()], limit));
+        assert!(!all_stale(&[stale.clone(), unknown], limit));
+
+        assert!(UnlockPolicy::StaleAfter(limit).should_unlock(std::slice::from_ref(&stale)));
+        assert!(!UnlockPolicy::StaleAfter(limit).should_unlock(std::slice::from_ref(&fresh)));
+        assert!(!UnlockPolicy::Never.should_unlock(std::slice::from_ref(&stale)));
+        assert!(UnlockPolicy::Decide(Arc::new(|locks: &[LockInfo]| locks.len() == 2)).should_unlock(&[stale.clone(), fresh.clone()]));
+
+        assert_eq!(holders(&[stale, fresh]), "srv1 PID 1, srv1 PID 2");
+    }
+}
@@ -1,65 +1,276 @@
-mod utils;
-mod config;
-mod backup;
-mod restore;
+mod cli;
 mod help;
+mod interactive;
+mod terminal;
 
 use std::env;
-use std::path::Path; // 引入 Path
+use std::process;
+use std::time::Instant;
+use cli::{CliCommand, RepoArgs};
+use xcompress::outln;
+use xcompress::{check, daemon, diff, history, restore, report, utils};
+use xcompress::config::{FinalRestoreConfig, PasswordSource};
+use xcompress::backup::DirectBackup;
+use xcompress::lock::UnlockPolicy;
+use xcompress::progress::{self, JobOutput};
+use xcompress::report::JobReport;
+use xcompress::{Error, ErrorKind};
 use console::style;
-use dialoguer::{theme::ColorfulTheme, Select};
+use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
 
 fn main() {
-    // 首次运行时先打印一次
-    let _ = console::Term::stdout().clear_screen();
-    utils::print_header();
+    // 1. 解析命令行参数
+    let args: Vec<String> = env::args().skip(1).collect();
+    let cli = match cli::parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{} {}", style("✖").red(), style(e).red().bold());
+            process::exit(report::EXIT_CONFIG_ERROR);
+        }
+    };
+
+    if let CliCommand::Help = cli.command {
+        help::print_cli_usage();
+        return;
+    }
+
+    // JSON 报告或差异输出到标准输出时，标准输出只包含 JSON: 不打印标题，其余输出改写到标准错误
+    if cli.json_on_stdout() {
+        progress::text_to_stderr();
+    } else {
+        // 交互模式下首次运行时先清屏再打印；子命令模式保留终端已有输出
+        if matches!(cli.command, CliCommand::Menu | CliCommand::Legacy(_)) {
+            let _ = console::Term::stdout().clear_screen();
+        }
+        terminal::print_header();
+    }
+
+    let command_name = cli.command.name();
+    let (exit_code, jobs) = run_command(cli.command, cli.no_pause);
+
+    if cli.report_json {
+        if let Err(e) = report::emit_json(command_name, exit_code, &jobs, cli.report_file.as_deref()) {
+            eprintln!("{} {}", style("✖").red(), style(e).red());
+        }
+    }
+
+    if !cli.no_pause {
+        wait_for_exit();
+    }
+    process::exit(exit_code);
+}
+
+/// 执行子命令，返回退出码与各任务的执行结果
+fn run_command(command: CliCommand, no_pause: bool) -> (i32, Vec<JobReport>) {
+    // 查看历史不需要 restic
+    if let CliCommand::History(args) = &command {
+        return match interactive::history::handle_history(&args.filter, args.details) {
+            Ok(()) => (report::EXIT_OK, Vec::new()),
+            Err(e) => {
+                terminal::print_error("✖ 操作失败:", &Error::from(e));
+                (report::EXIT_CONFIG_ERROR, Vec::new())
+            }
+        };
+    }
 
-    // 1. 检查 Restic 环境
-    let restic_exe_path = match utils::check_restic_path() {
+    // 2. 检查 Restic 环境
+    let restic_exe_path = match terminal::check_restic_path() {
         Ok(path) => path,
+        // 配置文件中的任务可以用 restic_binary 指定各自的 restic，解析配置之后再由未指定的任务报告缺失
+        Err(e) if e.kind() == ErrorKind::ResticMissing && command.reads_config() => String::new(),
         Err(e) => {
-            eprintln!("{}", e);
-            wait_for_exit();
-            return;
+            terminal::print_error("✖ 错误:", &e);
+            return (e.exit_code(), Vec::new());
         }
     };
 
-    // 2. 解析命令行参数
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        // 如果有参数，直接处理并退出，不显示菜单
-        let first_arg = &args[1];
-        if first_arg.ends_with(".toml") {
-            // 参数是 toml 配置文件，执行批量备份
-            backup::handle_backup(&restic_exe_path, Some(first_arg.clone()), None);
-        } else {
-            // 参数是普通路径，判断是仓库还是备份源
-            let path = Path::new(first_arg);
-            if utils::is_restic_repo(path) {
-                // 是一个 Restic 仓库 -> 启动恢复流程
-                println!("{} 检测到提供的路径是一个 Restic 仓库，进入恢复模式...", style("i").blue());
-                if let Err(e) = restore::handle_restore(&restic_exe_path, Some(first_arg.clone())) {
-                    eprintln!("\n{} {}", style("✖ 恢复操作失败:").red().bold(), style(e).red());
-                }
+    let result = match command {
+        // 如果没有参数，显示交互式主菜单
+        CliCommand::Menu => Ok(show_main_menu(&restic_exe_path)),
+        CliCommand::Legacy(first_arg) => run_legacy(&restic_exe_path, first_arg),
+        CliCommand::Backup(args) => run_backup_command(&restic_exe_path, args, no_pause),
+        CliCommand::Restore(args) => run_restore_command(&restic_exe_path, args, no_pause),
+        CliCommand::BatchBackup { config, jobs } => match config {
+            Some(path) => interactive::backup::handle_backup(&restic_exe_path, Some(path), None, jobs),
+            None if no_pause => Err(Error::config("", "batch-backup", "config", "非交互模式下 batch-backup 需要提供配置文件路径。")),
+            None => interactive::backup::handle_batch_backup(&restic_exe_path, jobs),
+        },
+        CliCommand::BatchRestore { config } => {
+            if config.is_none() && no_pause {
+                Err(Error::config("", "batch-restore", "config", "非交互模式下 batch-restore 需要提供配置文件路径。"))
             } else {
-                // 不是仓库 -> 视为备份源，启动备份流程
-                backup::handle_backup(&restic_exe_path, None, Some(first_arg.clone()));
+                interactive::restore::handle_batch_restore(&restic_exe_path, config)
             }
         }
+        CliCommand::Snapshots(args) => resolve_repo_args(args, no_pause).map(|(repo, passwd)| {
+            let started = Instant::now();
+            let result = interactive::restore::list_snapshots(&restic_exe_path, &repo, &passwd);
+            vec![single_report("snapshots", &repo, started, result.map(|_| String::new()))]
+        }),
+        CliCommand::Check(args) => resolve_repo_args(args.repo_args, no_pause).map(|(repo, passwd)| {
+            let started = Instant::now();
+            let output = JobOutput::default();
+            let unlock = if no_pause { UnlockPolicy::Never } else { interactive::ask_unlock().suspending(&output) };
+            let result = check::run_check(&restic_exe_path, &repo, &passwd, args.read_data_subset.as_deref(), unlock, &output);
+            if let Ok(output) = &result {
+                outln!("{}\n{}", style("✔ 仓库校验通过!").green().bold(), output);
+            }
+            vec![single_report("check", &repo, started, result.map(|_| "仓库校验通过。".to_string()))]
+        }),
+        CliCommand::Diff(args) => resolve_repo_args(args.repo_args, no_pause).map(|(repo, passwd)| {
+            let started = Instant::now();
+            let result = diff::run_diff(&restic_exe_path, &repo, &passwd, &args.from, &args.to, &JobOutput::default()).and_then(|diff| {
+                let diff = match &args.path {
+                    Some(path) => diff.filtered(path),
+                    None => diff,
+                };
+                interactive::diff::emit_json(&diff, args.output.as_deref())?;
+                Ok(diff.describe())
+            });
+            vec![single_report("diff", &repo, started, result)]
+        }),
+        CliCommand::Daemon { config, jobs } => daemon::run_daemon(&restic_exe_path, &config, jobs, &JobOutput::default()).map(|_| Vec::new()),
+        CliCommand::History(_) | CliCommand::Help => unreachable!(),
+    };
+
+    match result {
+        Ok(jobs) => (report::exit_code_for(&jobs), jobs),
+        Err(e) => {
+            terminal::print_error("✖ 操作失败:", &e);
+            (e.exit_code(), Vec::new())
+        }
+    }
+}
+
+/// 将单个操作的结果转换为任务报告，失败时同时打印错误
+fn single_report(job: &str, repo: &str, started: Instant, result: Result<String, Error>) -> JobReport {
+    if let Err(e) = &result {
+        terminal::print_error("✖ 操作失败:", e);
+    }
+    JobReport::new(job, repo, started, result.map(|msg| (msg, Vec::new())))
+}
+
+/// 旧版用法: 根据唯一的参数猜测要执行的操作
+fn run_legacy(restic_exe_path: &str, first_arg: String) -> Result<Vec<JobReport>, Error> {
+    if first_arg.ends_with(".toml") {
+        // 参数是 toml 配置文件，执行批量备份
+        interactive::backup::handle_backup(restic_exe_path, Some(first_arg), None, None)
     } else {
-        // 如果没有参数，显示交互式主菜单
-        show_main_menu(&restic_exe_path);
+        // 参数是普通路径，判断是仓库还是备份源
+        if utils::is_repo_location(&first_arg) {
+            // 是一个 Restic 仓库 -> 启动恢复流程
+            outln!("{} 检测到提供的路径是一个 Restic 仓库，进入恢复模式...", style("i").blue());
+            Ok(interactive_restore_report(restic_exe_path, Some(first_arg), None).into_iter().collect())
+        } else {
+            // 不是仓库 -> 视为备份源，启动备份流程
+            interactive::backup::handle_backup(restic_exe_path, None, Some(first_arg), None)
+        }
+    }
+}
+
+/// 运行交互式恢复并将结果转换为任务报告，用户取消时返回 `None`
+fn interactive_restore_report(restic_exe_path: &str, repo: Option<String>, passwd: Option<PasswordSource>) -> Option<JobReport> {
+    let started = Instant::now();
+    let repo_name = repo.clone().unwrap_or_default();
+    let result = interactive::restore::handle_restore(restic_exe_path, repo, passwd);
+    match &result {
+        // 用户取消时不生成报告，也不记录历史
+        Ok(false) => return None,
+        Ok(true) => {}
+        Err(e) => terminal::print_error("✖ 恢复操作失败:", e),
     }
-    
-    wait_for_exit();
+    let report = JobReport::new("interactive", &repo_name, started, result.map(|_| (String::new(), Vec::new())));
+    history::record("restore", std::slice::from_ref(&report), &JobOutput::default());
+    Some(report)
 }
 
-fn show_main_menu(restic_exe_path: &str) {
+/// `backup` 子命令: 参数齐全时直接备份，否则回退到交互式流程
+fn run_backup_command(restic_exe_path: &str, args: cli::BackupArgs, no_pause: bool) -> Result<Vec<JobReport>, Error> {
+    if let (Some(repo), Some(passwd)) = (args.repo_args.repo, args.repo_args.passwd) {
+        if args.paths.is_empty() {
+            return Err(Error::config("", "backup", "paths", "至少需要提供一个备份路径。"));
+        }
+        let backup = DirectBackup {
+            job_name: "cli".to_string(),
+            paths: args.paths,
+            repo,
+            passwd,
+            tag: args.tag.unwrap_or_default(),
+            pack_size: args.pack_size.unwrap_or(128),
+            compression: None,
+            exclude: Default::default(),
+            init: Default::default(),
+            unlock: UnlockPolicy::Never,
+        };
+        return Ok(vec![interactive::backup::run_direct_backup(restic_exe_path, &backup)]);
+    }
+
+    if no_pause {
+        return Err(Error::config("", "backup", "", "非交互模式下 backup 需要同时提供 --repo 与密码参数 (--passwd / --passwd-file / --passwd-command / --passwd-env)。"));
+    }
+    match args.paths.len() {
+        0 => interactive::backup::handle_backup(restic_exe_path, None, None, None),
+        1 => interactive::backup::handle_backup(restic_exe_path, None, args.paths.into_iter().next(), None),
+        _ => Err(Error::config("", "backup", "paths", "交互式备份一次只能指定一个路径，多个路径请同时提供 --repo 与密码参数。")),
+    }
+}
+
+/// `restore` 子命令: 参数齐全时直接恢复，否则回退到交互式流程
+fn run_restore_command(restic_exe_path: &str, args: cli::RestoreArgs, no_pause: bool) -> Result<Vec<JobReport>, Error> {
+    let RepoArgs { repo, passwd } = args.repo_args;
+    match (repo, passwd, args.target) {
+        (Some(repo), Some(passwd), Some(target)) => {
+            let job = FinalRestoreConfig {
+                job_name: "cli".to_string(),
+                repo,
+                target,
+                passwd,
+                snapshots: args.snapshot.unwrap_or_else(|| "latest".to_string()),
+                restore_path: args.restore_path.unwrap_or_default(),
+                output_format: args.format,
+                restic_binary: None,
+                env: Vec::new(),
+            };
+            Ok(restore::run_restore_jobs(restic_exe_path, vec![job], &JobOutput::default()))
+        }
+        _ if no_pause => Err(Error::config("", "restore", "", "非交互模式下 restore 需要同时提供 --repo、--target 与密码参数。")),
+        (repo, passwd, _) => Ok(interactive_restore_report(restic_exe_path, repo, passwd).into_iter().collect()),
+    }
+}
+
+/// 补全仓库路径与密码；非交互模式下缺失任何一项都直接报错
+fn resolve_repo_args(args: RepoArgs, no_pause: bool) -> Result<(String, PasswordSource), Error> {
+    let theme = ColorfulTheme::default();
+    let repo = match args.repo {
+        Some(repo) => repo,
+        None if no_pause => return Err(Error::config("", "", "repo", "非交互模式下需要提供 --repo 参数。")),
+        None => Input::with_theme(&theme)
+            .with_prompt("请输入或拖入 restic 仓库路径")
+            .interact_text()
+            .map_err(|e| e.to_string())?,
+    };
+    let passwd = match args.passwd {
+        Some(passwd) => passwd,
+        None if no_pause => return Err(Error::config("", "", "passwd", "非交互模式下需要提供密码参数 (--passwd / --passwd-file / --passwd-command / --passwd-env)。")),
+        None => PasswordSource::Plain(
+            Password::with_theme(&theme)
+                .with_prompt("请输入仓库密码")
+                .interact()
+                .map_err(|e| e.to_string())?,
+        ),
+    };
+    Ok((repo.trim().to_string(), passwd))
+}
+
+fn show_main_menu(restic_exe_path: &str) -> Vec<JobReport> {
     let items = &[
-        "备份 (Compress)", 
-        "恢复 (Decompress)", 
-        "批量备份 (Batch Backup)", 
+        "备份 (Compress)",
+        "恢复 (Decompress)",
+        "批量备份 (Batch Backup)",
         "批量恢复 (Batch Restore)",
+        "校验仓库 (Check)",
+        "比较快照 (Diff)",
+        "历史记录 (History)",
         "查看帮助 (View Help)",
         "退出 (Exit)"
     ];
@@ -73,54 +284,65 @@ fn show_main_menu(restic_exe_path: &str) {
             .interact_opt()
             .unwrap();
 
-        let mut should_exit_loop = false;
-
         match selection {
             Some(0) => { // 备份
-                backup::handle_backup(restic_exe_path, None, None);
-                should_exit_loop = true;
+                return interactive::backup::handle_backup(restic_exe_path, None, None, None).unwrap_or_else(|e| {
+                    terminal::print_error("✖ 备份操作失败:", &e);
+                    Vec::new()
+                });
             }
             Some(1) => { // 恢复
-                if let Err(e) = restore::handle_restore(restic_exe_path, None) {
-                    eprintln!("\n{} {}", style("✖ 恢复操作失败:").red().bold(), style(e).red());
-                }
-                should_exit_loop = true;
+                return interactive_restore_report(restic_exe_path, None, None).into_iter().collect();
             }
             Some(2) => { // 批量备份
-                if let Err(e) = backup::handle_batch_backup(restic_exe_path) {
-                    eprintln!("\n{} {}", style("✖ 批量备份操作失败:").red().bold(), style(e).red());
-                }
-                should_exit_loop = true;
+                return interactive::backup::handle_batch_backup(restic_exe_path, None).unwrap_or_else(|e| {
+                    terminal::print_error("✖ 批量备份操作失败:", &e);
+                    Vec::new()
+                });
             }
             Some(3) => { // 批量恢复
-                if let Err(e) = restore::handle_batch_restore(restic_exe_path) {
-                    eprintln!("\n{} {}", style("✖ 批量恢复操作失败:").red().bold(), style(e).red());
+                return interactive::restore::handle_batch_restore(restic_exe_path, None).unwrap_or_else(|e| {
+                    terminal::print_error("✖ 批量恢复操作失败:", &e);
+                    Vec::new()
+                });
+            }
+            Some(4) => { // 校验仓库
+                let started = Instant::now();
+                let result = interactive::check::handle_check(restic_exe_path);
+                let repo = result.as_ref().map(|(repo, _)| repo.clone()).unwrap_or_default();
+                return vec![single_report("check", &repo, started, result.map(|_| "仓库校验通过。".to_string()))];
+            }
+            Some(5) => { // 比较快照
+                if let Err(e) = interactive::diff::handle_diff(restic_exe_path) {
+                    terminal::print_error("✖ 比较快照失败:", &e);
+                }
+                // 不退出循环，返回主菜单
+            }
+            Some(6) => { // 历史记录
+                if let Err(e) = interactive::history::handle_history_menu() {
+                    eprintln!("{} {}", style("✖").red(), style(e).red().bold());
                 }
-                should_exit_loop = true;
+                // 不退出循环，返回主菜单
             }
-            Some(4) => { // 查看帮助
+            Some(7) => { // 查看帮助
                 let _ = console::Term::stdout().clear_screen();
                 help::print_help_info();
                 let _ = console::Term::stdout().clear_screen();
-                utils::print_header();
+                terminal::print_header();
                 // 不退出循环，返回主菜单
             }
-            Some(5) | None => { // 退出
-                println!("\n{}", style("👋 程序已退出，感谢使用！").yellow());
-                return; // 直接退出函数
+            Some(8) | None => { // 退出
+                outln!("\n{}", style("👋 程序已退出，感谢使用！").yellow());
+                return Vec::new(); // 直接退出函数
             }
             _ => unreachable!(),
         }
-
-        if should_exit_loop {
-            break;
-        }
     }
 }
 
 
 fn wait_for_exit() {
-    println!("\n\n{}", style("操作完成，按 Enter 键退出...").dim());
+    outln!("\n\n{}", style("操作完成，按 Enter 键退出...").dim());
     let mut buffer = String::new();
     std::io::stdin().read_line(&mut buffer).unwrap();
-}
\ No newline at end of file
+}
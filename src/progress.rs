@@ -0,0 +1,276 @@
+use crate::utils::format_bytes;
+use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, WeakProgressBar};
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 人类可读的输出是否写到标准错误，见 `text_to_stderr`
+static TEXT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// 之后所有人类可读的输出 (`outln!`) 都写到标准错误，标准输出只留给机器可读的结果，
+/// 例如输出到标准输出的 JSON 报告
+pub fn text_to_stderr() {
+    TEXT_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// 人类可读的输出当前是否写到标准错误
+pub fn is_text_on_stderr() -> bool {
+    TEXT_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// 与 `println!` 相同，但调用过 `progress::text_to_stderr` 之后写到标准错误
+#[macro_export]
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if $crate::progress::is_text_on_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 备份进行中的状态消息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupStatus {
+    pub percent_done: f64,
+    pub seconds_remaining: Option<u64>,
+    pub total_files: u64,
+    pub files_done: u64,
+    pub total_bytes: u64,
+    pub bytes_done: u64,
+    pub current_files: Vec<String>,
+}
+
+/// 备份过程中通过回调报告的事件
+#[derive(Debug, Clone)]
+pub enum BackupEvent {
+    /// 进度更新
+    Status(BackupStatus),
+    /// 单个文件读取失败，备份会继续进行
+    Error { item: String, message: String },
+}
+
+/// 备份结束时 restic 输出的汇总信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSummary {
+    pub files_new: u64,
+    pub files_changed: u64,
+    pub files_unmodified: u64,
+    pub dirs_new: u64,
+    pub dirs_changed: u64,
+    pub dirs_unmodified: u64,
+    pub data_added: u64,
+    pub data_added_packed: u64,
+    pub total_files_processed: u64,
+    pub total_bytes_processed: u64,
+    pub total_duration: f64,
+    pub snapshot_id: Option<String>,
+    /// 无法读取、未能备份的文件数 (restic 退出码为 3，快照中缺少这些文件)
+    #[serde(skip)]
+    pub read_errors: u64,
+}
+
+impl BackupSummary {
+    /// 快照短 ID (前 8 位)
+    pub fn short_id(&self) -> Option<String> {
+        self.snapshot_id.as_ref().map(|id| id.chars().take(8).collect())
+    }
+
+    /// 新增数据的压缩比 (压缩前 / 实际存储)，没有新增数据或 restic 未报告存储大小时返回 `None`
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.data_added > 0 && self.data_added_packed > 0).then(|| self.data_added as f64 / self.data_added_packed as f64)
+    }
+
+    /// 单行的人类可读描述
+    pub fn describe(&self) -> String {
+        let ratio = self.compression_ratio().map(|r| format!("，压缩比 {:.2}x", r)).unwrap_or_default();
+        let read_errors = if self.read_errors > 0 { format!("，{} 个文件无法读取、未备份", self.read_errors) } else { String::new() };
+        format!(
+            "快照 {}: 新增 {} / 修改 {} / 未变 {} 个文件，处理 {}，新增数据 {} (存储 {}{})，耗时 {:.1}s{}",
+            self.short_id().unwrap_or_else(|| "-".to_string()),
+            self.files_new,
+            self.files_changed,
+            self.files_unmodified,
+            format_bytes(self.total_bytes_processed),
+            format_bytes(self.data_added),
+            format_bytes(self.data_added_packed),
+            ratio,
+            self.total_duration,
+            read_errors
+        )
+    }
+}
+
+/// 接收库中流程输出的文本行，见 `JobOutput::with_sink`
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// 批量任务的输出目标
+///
+/// 库中的流程只通过它输出文本。默认直接打印到终端；并行执行时所有文本和进度条都经由同一个
+/// `MultiProgress` 输出，文本行前带上任务名，避免多个任务的输出互相覆盖。
+#[derive(Clone, Default)]
+pub struct JobOutput {
+    multi: Option<MultiProgress>,
+    label: Option<String>,
+    /// 顺序执行时加入的进度条，`suspend` 时暂时隐藏
+    bars: Arc<Mutex<Vec<WeakProgressBar>>>,
+    sink: Option<OutputSink>,
+}
+
+impl JobOutput {
+    /// 把文本行交给 `sink` 而不是打印到终端，也不显示进度条 (嵌入到其它程序时使用)
+    pub fn with_sink(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self { sink: Some(Arc::new(sink)), ..Default::default() }
+    }
+
+    /// 并行执行时共享的输出，沿用当前输出的 sink
+    pub fn parallel(&self) -> Self {
+        let multi = match self.sink {
+            Some(_) => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+            None => MultiProgress::new(),
+        };
+        Self { multi: Some(multi), sink: self.sink.clone(), ..Default::default() }
+    }
+
+    /// 某个任务专用的输出，仅在并行执行时带上任务名
+    pub fn for_job(&self, label: &str) -> Self {
+        Self {
+            multi: self.multi.clone(),
+            label: self.multi.as_ref().map(|_| label.to_string()),
+            bars: self.bars.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+
+    /// 在进度条上方打印一行文本 (并行执行时省略行首的空行)
+    pub fn println(&self, msg: impl Display) {
+        match (&self.multi, &self.sink) {
+            (Some(_), Some(sink)) => sink(&self.labeled(msg)),
+            (Some(multi), None) => {
+                let line = self.labeled(msg);
+                multi.suspend(|| outln!("{}", line));
+            }
+            (None, Some(sink)) => sink(&msg.to_string()),
+            (None, None) => outln!("{}", msg),
+        }
+    }
+
+    fn labeled(&self, msg: impl Display) -> String {
+        let msg = msg.to_string();
+        let msg = msg.trim_start_matches('\n');
+        match &self.label {
+            Some(label) => format!("{} {}", style(format!("[{}]", label)).cyan(), msg),
+            None => msg.to_string(),
+        }
+    }
+
+    /// 将进度条加入共享的显示区域 (顺序执行时原样返回)
+    pub fn add(&self, bar: ProgressBar) -> ProgressBar {
+        match &self.multi {
+            Some(multi) => multi.add(bar),
+            None if self.sink.is_some() => {
+                bar.set_draw_target(ProgressDrawTarget::hidden());
+                bar
+            }
+            None => {
+                let mut bars = self.bars.lock().unwrap();
+                bars.retain(|weak| weak.upgrade().is_some());
+                bars.push(bar.downgrade());
+                bar
+            }
+        }
+    }
+
+    /// 暂时隐藏所有进度条执行 `f` (例如等待用户输入)，结束后重新绘制
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> T {
+        match &self.multi {
+            Some(multi) => multi.suspend(f),
+            None => {
+                let bars: Vec<ProgressBar> = self.bars.lock().unwrap().iter().filter_map(WeakProgressBar::upgrade).collect();
+                suspend_all(&bars, f)
+            }
+        }
+    }
+
+    /// 进度条消息的前缀，并行执行时为任务名
+    pub fn prefix(&self) -> String {
+        self.label.as_ref().map(|label| format!("[{}] ", label)).unwrap_or_default()
+    }
+}
+
+fn suspend_all<T>(bars: &[ProgressBar], f: impl FnOnce() -> T) -> T {
+    match bars.split_first() {
+        Some((bar, rest)) => bar.suspend(|| suspend_all(rest, f)),
+        None => f(),
+    }
+}
+
+/// 在任务输出中显示旋转等待提示，直到 `f` 执行完毕
+pub fn with_spinner<T>(output: &JobOutput, f: impl FnOnce() -> T) -> T {
+    let spinner = output.add(ProgressBar::new_spinner());
+    spinner.set_style(ProgressStyle::default_spinner()
+        .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", " "])
+        .template("{spinner:.cyan} {msg}").unwrap());
+    spinner.set_message(format!("{}正在执行 restic 命令，请稍候...", output.prefix()));
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    let result = f();
+    spinner.finish_and_clear();
+    result
+}
+
+/// 将 `ResticClient::backup` 报告的事件渲染为终端进度条
+pub struct BackupProgress {
+    bar: ProgressBar,
+    output: JobOutput,
+}
+
+impl BackupProgress {
+    pub fn start(output: &JobOutput) -> Self {
+        let bar = output.add(ProgressBar::new(1000));
+        bar.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.cyan} [{elapsed_precise}] [{bar:30.cyan/blue}] {msg}\n  {prefix:.dim}").unwrap()
+            .progress_chars("=> "));
+        bar.set_message(format!("{}正在启动 restic 备份...", output.prefix()));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self { bar, output: output.clone() }
+    }
+
+    pub fn handle(&self, event: BackupEvent) {
+        match event {
+            BackupEvent::Status(status) => render_status(&self.bar, &status, &self.output.prefix()),
+            BackupEvent::Error { item, message } => self.output.println(format!("{} {}: {}", style("!").yellow(), item, message)),
+        }
+    }
+
+    pub fn finish(self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn render_status(bar: &ProgressBar, status: &BackupStatus, prefix: &str) {
+    bar.set_position((status.percent_done.clamp(0.0, 1.0) * 1000.0) as u64);
+
+    // restic 尚未统计完备份总量时只显示已处理的部分
+    let files = if status.total_files > 0 {
+        format!("{}/{} 文件", status.files_done, status.total_files)
+    } else {
+        format!("{} 文件", status.files_done)
+    };
+    let bytes = if status.total_bytes > 0 {
+        format!("{}/{}", format_bytes(status.bytes_done), format_bytes(status.total_bytes))
+    } else {
+        format_bytes(status.bytes_done)
+    };
+    let eta = match status.seconds_remaining {
+        Some(secs) => format!("剩余 {:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+        None => "剩余 --:--:--".to_string(),
+    };
+    bar.set_message(format!("{}{:.1}% | {} | {} | {}", prefix, status.percent_done * 100.0, files, bytes, eta));
+    bar.set_prefix(status.current_files.first().cloned().unwrap_or_default());
+}
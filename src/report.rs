@@ -0,0 +1,145 @@
+use crate::error::{Error, ErrorKind};
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// 进程退出码
+pub const EXIT_OK: i32 = 0;
+/// 至少有一个任务失败
+pub const EXIT_PARTIAL_FAILURE: i32 = 1;
+/// 命令行参数或配置文件错误
+pub const EXIT_CONFIG_ERROR: i32 = 2;
+/// 未找到 restic 可执行文件
+pub const EXIT_RESTIC_MISSING: i32 = 3;
+/// 仓库密码错误
+pub const EXIT_WRONG_PASSWORD: i32 = 4;
+/// 仓库不存在
+pub const EXIT_REPO_NOT_FOUND: i32 = 5;
+/// 仓库被锁定
+pub const EXIT_REPO_LOCKED: i32 = 6;
+/// 权限不足
+pub const EXIT_PERMISSION_DENIED: i32 = 7;
+/// 磁盘空间不足
+pub const EXIT_DISK_FULL: i32 = 8;
+/// 网络或存储后端错误
+pub const EXIT_BACKEND_ERROR: i32 = 9;
+/// restic 版本过旧
+pub const EXIT_RESTIC_TOO_OLD: i32 = 10;
+
+/// 单个任务的执行结果: 成功时为摘要与快照 ID，失败时为错误信息
+pub type JobResult = Result<(String, Vec<String>), Error>;
+
+/// 单个任务的执行结果，用于生成机器可读的运行报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobReport {
+    pub job: String,
+    pub repo: String,
+    pub success: bool,
+    pub snapshot_ids: Vec<String>,
+    pub duration_secs: f64,
+    pub message: String,
+    pub error: Option<String>,
+    /// 失败时的错误类别
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+    /// 开始与结束时间 (本地时间，RFC 3339)
+    #[serde(default)]
+    pub started_at: String,
+    #[serde(default)]
+    pub finished_at: String,
+    /// 备份源路径 (仅备份任务)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// 本次新增到仓库的数据量 (未压缩，仅备份任务)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_added: Option<u64>,
+}
+
+impl JobReport {
+    /// 根据任务结果生成报告，`started` 为任务开始时间
+    pub fn new(job: &str, repo: &str, started: Instant, result: JobResult) -> Self {
+        let elapsed = started.elapsed();
+        let duration_secs = elapsed.as_secs_f64();
+        let finished_at = Local::now();
+        let started_at = finished_at - chrono::Duration::from_std(elapsed).unwrap_or_default();
+        let started_at = started_at.to_rfc3339_opts(SecondsFormat::Secs, false);
+        let finished_at = finished_at.to_rfc3339_opts(SecondsFormat::Secs, false);
+        match result {
+            Ok((message, snapshot_ids)) => JobReport {
+                job: job.to_string(),
+                repo: repo.to_string(),
+                success: true,
+                snapshot_ids,
+                duration_secs,
+                message,
+                error: None,
+                error_kind: None,
+                started_at,
+                finished_at,
+                sources: Vec::new(),
+                bytes_added: None,
+            },
+            Err(e) => JobReport {
+                job: job.to_string(),
+                repo: repo.to_string(),
+                success: false,
+                snapshot_ids: Vec::new(),
+                duration_secs,
+                message: String::new(),
+                error: Some(e.to_string()),
+                error_kind: Some(e.kind()),
+                started_at,
+                finished_at,
+                sources: Vec::new(),
+                bytes_added: None,
+            },
+        }
+    }
+
+    /// 记录备份源路径
+    pub fn with_sources(mut self, sources: &[String]) -> Self {
+        self.sources = sources.to_vec();
+        self
+    }
+
+    /// 记录新增数据量
+    pub fn with_bytes_added(mut self, bytes_added: Option<u64>) -> Self {
+        self.bytes_added = bytes_added;
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport<'a> {
+    pub command: &'a str,
+    pub exit_code: i32,
+    pub jobs: &'a [JobReport],
+}
+
+/// 根据所有任务的结果计算退出码
+///
+/// 第一个能归类的失败任务决定退出码 (如密码错误、仓库被锁定)，其余失败为 `EXIT_PARTIAL_FAILURE`。
+pub fn exit_code_for(jobs: &[JobReport]) -> i32 {
+    let failed: Vec<&JobReport> = jobs.iter().filter(|j| !j.success).collect();
+    if failed.is_empty() {
+        return EXIT_OK;
+    }
+    failed
+        .iter()
+        .filter_map(|j| j.error_kind.map(ErrorKind::exit_code))
+        .find(|&code| code != EXIT_PARTIAL_FAILURE)
+        .unwrap_or(EXIT_PARTIAL_FAILURE)
+}
+
+/// 将运行报告序列化为 JSON，输出到文件或标准输出
+pub fn emit_json(command: &str, exit_code: i32, jobs: &[JobReport], file: Option<&str>) -> Result<(), String> {
+    let report = RunReport { command, exit_code, jobs };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| format!("生成 JSON 报告失败: {}", e))?;
+    match file {
+        Some(path) => std::fs::write(path, json).map_err(|e| format!("写入报告文件 '{}' 失败: {}", path, e)),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use regex::Regex;
+use std::time::Duration;
+
+/// 查找下一次触发时间时最多检查的步数 (按月/日/时跳跃，足以覆盖数年)
+const MAX_SEARCH_STEPS: usize = 100_000;
+
+/// 备份任务的运行计划
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// 固定间隔，例如 "every 6h"、"30m"、"1h30m"
+    Interval(Duration),
+    /// 5 段 cron 表达式: 分 时 日 月 周，例如 "0 3 * * *"
+    Cron(CronSpec),
+}
+
+/// 解析后的 cron 表达式，每个字段是允许取值的位掩码
+#[derive(Debug, Clone)]
+pub struct CronSpec {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日与周同时被限制时，按 cron 惯例满足其一即可
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    /// 解析 `schedule` 字段: 间隔 ("every 6h"、"90m"、"1d")、cron 表达式或 @hourly/@daily/@weekly/@monthly
+    pub fn parse(value: &str) -> Result<Schedule, String> {
+        let value = value.trim();
+        let expr = match value {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        if expr.split_whitespace().count() == 5 {
+            return CronSpec::parse(expr).map(Schedule::Cron);
+        }
+        parse_interval(expr).map(Schedule::Interval)
+    }
+
+    /// `after` 之后的下一次触发时间
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Interval(interval) => Some(after + ChronoDuration::from_std(*interval).ok()?),
+            Schedule::Cron(spec) => spec.next_after(after),
+        }
+    }
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let expr = value.strip_prefix("every").map_or(value, str::trim_start);
+    let interval = parse_duration(expr)
+        .ok_or_else(|| format!("无法识别的 schedule: '{}' (示例: \"every 6h\"、\"30m\"、\"0 3 * * *\"、\"@daily\")", value))?;
+    if interval.as_secs() < 60 {
+        return Err(format!("schedule 的间隔不能小于 1 分钟: '{}'", value));
+    }
+    Ok(interval)
+}
+
+/// 解析时长，例如 "30m"、"6h"、"1h30m"、"2d" (单位 s/m/h/d，可组合)
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let re = Regex::new(r"^((?:\d+\s*[smhd]\s*)+)$").unwrap();
+    let caps = re.captures(value.trim())?;
+
+    let part_re = Regex::new(r"(\d+)\s*([smhd])").unwrap();
+    let mut secs: u64 = 0;
+    for part in part_re.captures_iter(&caps[1]) {
+        let n: u64 = part[1].parse().ok()?;
+        let unit = match &part[2] {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            _ => 86400,
+        };
+        secs = secs.saturating_add(n.saturating_mul(unit));
+    }
+    Some(Duration::from_secs(secs))
+}
+
+impl CronSpec {
+    fn parse(expr: &str) -> Result<CronSpec, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let err = |e: String| format!("cron 表达式 '{}' 无效: {}", expr, e);
+        let mut weekdays = parse_field(fields[4], 0, 7).map_err(err)?;
+        // 7 与 0 都表示星期日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        let spec = CronSpec {
+            minutes: parse_field(fields[0], 0, 59).map_err(err)?,
+            hours: parse_field(fields[1], 0, 23).map_err(err)?,
+            days: parse_field(fields[2], 1, 31).map_err(err)?,
+            months: parse_field(fields[3], 1, 12).map_err(err)?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        };
+        if spec.next_after(Local::now()).is_none() {
+            return Err(format!("cron 表达式 '{}' 永远不会触发。", expr));
+        }
+        Ok(spec)
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let mut t: NaiveDateTime = start;
+        for _ in 0..MAX_SEARCH_STEPS {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+            } else {
+                // 夏令时跳过的时间点不存在，继续向后查找
+                match Local.from_local_datetime(&t).earliest() {
+                    Some(local) => return Some(local),
+                    None => t += ChronoDuration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+/// 解析 cron 的单个字段，支持 `*`、`a`、`a-b`、`*/n`、`a-b/n`、`a/n` 以及逗号分隔的组合
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("步长 '{}' 无效", step))?;
+                if step == 0 {
+                    return Err("步长不能为 0".to_string());
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let parse_num = |s: &str| -> Result<u32, String> {
+            let n: u32 = s.parse().map_err(|_| format!("'{}' 不是数字", s))?;
+            if n < min || n > max {
+                return Err(format!("{} 超出范围 {}-{}", n, min, max));
+            }
+            Ok(n)
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (parse_num(a)?, parse_num(b)?),
+                // "a/n" 表示从 a 开始到最大值每隔 n
+                None if step.is_some() => (parse_num(range)?, max),
+                None => {
+                    let n = parse_num(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("范围 '{}' 无效", range));
+        }
+        for v in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).single().unwrap()
+    }
+
+    fn next(expr: &str, after: DateTime<Local>) -> DateTime<Local> {
+        Schedule::parse(expr).unwrap().next_after(after).unwrap()
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration(" 2d "), Some(Duration::from_secs(2 * 86400)));
+        assert_eq!(parse_duration("1h 15s"), Some(Duration::from_secs(3615)));
+        assert_eq!(parse_duration("6"), None);
+        assert_eq!(parse_duration("6w"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert!(matches!(Schedule::parse("every 6h"), Ok(Schedule::Interval(d)) if d == Duration::from_secs(6 * 3600)));
+        assert!(matches!(Schedule::parse("90m"), Ok(Schedule::Interval(d)) if d == Duration::from_secs(90 * 60)));
+        assert!(Schedule::parse("every 30s").is_err());
+        assert!(Schedule::parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn interval_runs_after_the_given_time() {
+        assert_eq!(next("every 6h", at(2026, 1, 1, 10, 0)), at(2026, 1, 1, 16, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_cron_expressions() {
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("0 24 * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        // 2 月 31 日永远不会出现
+        assert!(Schedule::parse("0 0 31 2 *").is_err());
+    }
+
+    #[test]
+    fn daily_cron_runs_next_day_once_passed() {
+        assert_eq!(next("0 3 * * *", at(2026, 1, 1, 10, 0)), at(2026, 1, 2, 3, 0));
+        assert_eq!(next("0 3 * * *", at(2026, 1, 1, 2, 59)), at(2026, 1, 1, 3, 0));
+        // 恰好在触发时间点上时取下一次
+        assert_eq!(next("0 3 * * *", at(2026, 1, 1, 3, 0)), at(2026, 1, 2, 3, 0));
+        assert_eq!(next("@daily", at(2026, 1, 1, 10, 0)), at(2026, 1, 2, 0, 0));
+    }
+
+    #[test]
+    fn cron_steps_and_lists() {
+        assert_eq!(next("*/15 * * * *", at(2026, 1, 1, 10, 7)), at(2026, 1, 1, 10, 15));
+        assert_eq!(next("0 8,20 * * *", at(2026, 1, 1, 9, 0)), at(2026, 1, 1, 20, 0));
+        assert_eq!(next("30 9-17/4 * * *", at(2026, 1, 1, 14, 0)), at(2026, 1, 1, 17, 30));
+        assert_eq!(next("@monthly", at(2026, 12, 15, 0, 0)), at(2027, 1, 1, 0, 0));
+    }
+
+    #[test]
+    fn cron_weekdays() {
+        // 2026-01-01 是星期四
+        assert_eq!(next("0 9 * * 1", at(2026, 1, 1, 10, 0)), at(2026, 1, 5, 9, 0));
+        // 0 与 7 都表示星期日
+        assert_eq!(next("0 0 * * 7", at(2026, 1, 1, 10, 0)), at(2026, 1, 4, 0, 0));
+        assert_eq!(next("0 0 * * 0", at(2026, 1, 1, 10, 0)), at(2026, 1, 4, 0, 0));
+    }
+
+    #[test]
+    fn cron_day_and_weekday_match_either() {
+        // 同时限制日与周时满足其一即可: 1 月 2 日是星期五
+        assert_eq!(next("0 0 13 * 5", at(2026, 1, 1, 10, 0)), at(2026, 1, 2, 0, 0));
+        // 只限制日时按日匹配
+        assert_eq!(next("0 0 13 * *", at(2026, 1, 1, 10, 0)), at(2026, 1, 13, 0, 0));
+    }
+}
@@ -0,0 +1,70 @@
+use std::env;
+use std::path::Path;
+use console::style;
+use xcompress::outln;
+use xcompress::report::JobReport;
+use xcompress::utils::{find_restic, RESTIC_ENV_OVERRIDE};
+use xcompress::version::{self, RECOMMENDED};
+use xcompress::{Error, ErrorKind};
+
+/// 打印彩色标题
+pub fn print_header() {
+    let version = env!("CARGO_PKG_VERSION");
+    let border = "=======================================================================";
+    outln!("\n{}\n", style(border).magenta());
+    outln!("{}", style(format!("        xcompress v{} - 您的 restic 备份/恢复助手", version)).cyan().bold());
+    outln!("        {}\n", style("作者: 菜玖玖emoji | Bilibili: space.bilibili.com/395819372").yellow());
+    outln!("{}\n", style(border).magenta());
+}
+
+/// 打印错误信息，以及对应的处理建议 (如果有)
+pub fn print_error(title: &str, error: &Error) {
+    eprintln!("\n{} {}", style(title).red().bold(), style(error).red());
+    if let Some(hint) = error.hint() {
+        eprintln!("{} {}", style("提示:").yellow().bold(), style(hint).yellow());
+    }
+}
+
+/// 打印失败任务报告中的错误与处理建议，与 `print_error` 格式相同
+pub fn print_report_error(title: &str, report: &JobReport) {
+    eprintln!("\n{} {}", style(title).red().bold(), style(report.error.as_deref().unwrap_or_default()).red());
+    if let Some(hint) = report.error_kind.and_then(ErrorKind::hint) {
+        eprintln!("{} {}", style("提示:").yellow().bold(), style(hint).yellow());
+    }
+}
+
+/// 检查 restic 是否可用且版本受支持，并返回其路径
+/// 查找顺序见 `utils::find_restic`
+/// 版本低于推荐版本时提示升级，无法识别版本时按最新版本处理
+/// 返回值:
+/// - Ok(String): restic 的可执行路径
+/// - Err(Error::ResticMissing): 未找到 restic
+/// - Err(Error::ResticTooOld): restic 版本低于 `version::MIN_SUPPORTED`
+pub fn check_restic_path() -> Result<String, Error> {
+    let override_path = env::var(RESTIC_ENV_OVERRIDE).ok().filter(|p| !p.trim().is_empty());
+    let path = find_restic().ok_or_else(|| match &override_path {
+        Some(p) => Error::ResticMissing.with_message(|e| format!("{} ({} 指定的 '{}' 无法执行)", e, RESTIC_ENV_OVERRIDE, p)),
+        None => Error::ResticMissing,
+    })?;
+    let detected = version::detect(&path);
+    let version_text = detected.map(|v| format!(" {}", v)).unwrap_or_default();
+    if override_path.is_some() {
+        outln!("{} {}", style("✔").green(), style(format!("使用 {} 指定的 restic{}: {}", RESTIC_ENV_OVERRIDE, version_text, path)).dim());
+    } else if path == "restic" {
+        outln!("{} {}", style("✔").green(), style(format!("检测到系统 PATH 中的 restic{}，将使用系统版本。", version_text)).dim());
+    } else {
+        let binary_name = Path::new(&path).file_name().unwrap_or_default().to_string_lossy().into_owned();
+        outln!("{} {}", style("✔").green(), style(format!("检测到程序目录中的 {}{}，将优先使用。", binary_name, version_text)).dim());
+    }
+
+    match detected {
+        Some(v) => {
+            v.ensure_supported()?;
+            if v < RECOMMENDED {
+                outln!("{} restic {} 低于推荐版本 {}，建议升级。", style("!").yellow(), v, RECOMMENDED);
+            }
+        }
+        None => outln!("{} 无法识别 restic 版本，将按最新版本处理。", style("!").yellow()),
+    }
+    Ok(path)
+}
@@ -1,32 +1,33 @@
+use std::cell::RefCell;
 use std::env;
 use std::io::Write;
 use std::path::Path;
-use std::process::{Command, Stdio};
-use console::{style, Term};
-use indicatif::{ProgressBar, ProgressStyle};
-use lazy_static::lazy_static;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use crate::config::PasswordSource;
+use crate::error::Error;
+use crate::progress::{with_spinner, JobOutput};
 use regex::Regex;
 
-lazy_static! {
-    static ref TERM: Term = Term::stdout();
+thread_local! {
+    /// 当前线程上正在执行的任务为 restic 设置的额外环境变量，见 `with_restic_env`
+    static RESTIC_ENV: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
 }
 
-/// 打印彩色标题
-pub fn print_header() {
-    let version = env!("CARGO_PKG_VERSION");
-    let border = "=======================================================================";
-    println!("\n{}\n", style(border).magenta());
-    println!("{}", style(format!("        xcompress v{} - 您的 restic 备份/恢复助手", version)).cyan().bold());
-    println!("        {}\n", style("作者: 菜玖玖emoji | Bilibili: space.bilibili.com/395819372").yellow());
-    println!("{}\n", style(border).magenta());
-}
+/// 指定 restic 可执行文件的环境变量，优先于自动查找
+pub const RESTIC_ENV_OVERRIDE: &str = "XCOMPRESS_RESTIC";
+
+/// 查找 restic，不输出任何内容；在系统 PATH 中找到时返回 "restic"
+///
+/// 查找顺序: 1. 环境变量 XCOMPRESS_RESTIC; 2. 程序同目录下的 restic/restic.exe; 3. 系统 PATH
+///
+/// 设置了 XCOMPRESS_RESTIC 时只使用它指定的程序，无法执行时返回 `None`，不再回退到自动查找。
+pub fn find_restic() -> Option<String> {
+    if let Some(path) = env::var(RESTIC_ENV_OVERRIDE).ok().filter(|p| !p.trim().is_empty()) {
+        let runs = Command::new(&path).arg("version").output().is_ok_and(|o| o.status.success());
+        return runs.then_some(path);
+    }
 
-/// 检查 restic 是否可用，并返回其路径
-/// 检查顺序: 1. 程序同目录下的 restic/restic.exe; 2. 系统 PATH
-/// 返回值:
-/// - Ok(String): restic 的可执行路径
-/// - Err(String): 未找到 restic 的错误信息
-pub fn check_restic_path() -> Result<String, String> {
     // 优先查找当前程序所在目录下是否有 restic 可执行文件
     if let Ok(exe_path) = env::current_exe() {
         if let Some(script_dir) = exe_path.parent() {
@@ -39,34 +40,24 @@ pub fn check_restic_path() -> Result<String, String> {
             let secondary_path = script_dir.join(secondary_name);
 
             if primary_path.exists() {
-                println!("{} {}", style("✔").green(), style(format!("检测到程序目录中的 {}，将优先使用。", binary_name)).dim());
-                return Ok(primary_path.to_string_lossy().into_owned());
+                return Some(primary_path.to_string_lossy().into_owned());
             } else if secondary_path.exists() {
-                println!("{} {}", style("✔").green(), style(format!("检测到程序目录中的 {}，将优先使用。", secondary_name)).dim());
-                return Ok(secondary_path.to_string_lossy().into_owned());
+                return Some(secondary_path.to_string_lossy().into_owned());
             }
         }
     }
     
     // 检查系统 PATH 中是否有 restic 命令
-    let output = Command::new("restic").arg("version").output();
-    if let Ok(output) = output {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let re = Regex::new(r"restic \d+\.").unwrap();
-            // 只要包含 restic 版本信息即可
-            if re.is_match(&stdout) || stdout.contains("restic") {
-                println!("{} {}", style("✔").green(), style("检测到系统 PATH 中的 restic，将使用系统版本。").dim());
-                return Ok("restic".to_string());
-            }
+    let output = Command::new("restic").arg("version").output().ok()?;
+    if output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let re = Regex::new(r"restic \d+\.").unwrap();
+        // 只要包含 restic 版本信息即可
+        if re.is_match(&stdout) || stdout.contains("restic") {
+            return Some("restic".to_string());
         }
     }
-
-    Err(format!(
-        "{} {}",
-        style("✖").red(),
-        style("错误: 未找到 restic 环境。\n请将 restic 可执行文件(Linux下通常为 'restic', Windows下为 'restic.exe') 放置于本程序同目录下，或将其路径添加到系统 PATH 环境变量中。").red().bold()
-    ))
+    None
 }
 
 /// 格式化字节大小为可读的字符串
@@ -89,6 +80,47 @@ pub fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// 格式化时长为可读的字符串，只保留最大的两个单位，例如 "2 小时 5 分钟"
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let units = [(86400, "天"), (3600, "小时"), (60, "分钟"), (1, "秒")];
+    let parts: Vec<String> = units
+        .iter()
+        .scan(secs, |rest, &(unit, name)| {
+            let n = *rest / unit;
+            *rest %= unit;
+            Some((n, name))
+        })
+        .skip_while(|(n, _)| *n == 0)
+        .take(2)
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, name)| format!("{} {}", n, name))
+        .collect();
+    if parts.is_empty() {
+        "0 秒".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// 解析 restic 风格的大小字符串 (例如 "512", "100K", "500M", "2G", "1T")，返回字节数
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let multiplier: u64 = match unit {
+        'B' => 1,
+        'K' => 1024,
+        'M' => 1024 * 1024,
+        'G' => 1024 * 1024 * 1024,
+        'T' => 1024 * 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
 /// 将标准系统路径转换为 restic 在 restore <id>:"/path" 中接受的格式
 /// Windows: D:\work\project -> /D/work/project
 /// Linux: /home/user -> /home/user (无变化)
@@ -113,6 +145,20 @@ pub fn convert_to_restic_path(p: &Path) -> String {
 }
 
 
+/// restic 远程存储后端的仓库地址前缀
+const REMOTE_REPO_PREFIXES: [&str; 8] = ["rest:", "sftp:", "s3:", "b2:", "azure:", "gs:", "swift:", "rclone:"];
+
+/// 判断仓库地址是否为远程后端的 URL，例如 "rest:http://host:8000/repo" 或 "sftp:user@host:/srv/repo"
+pub fn is_remote_repo(repo: &str) -> bool {
+    REMOTE_REPO_PREFIXES.iter().any(|prefix| repo.trim().starts_with(prefix))
+}
+
+/// 判断 `repo` 是否可以作为仓库使用: 本地路径按 `is_restic_repo` 严格检查；
+/// 远程 URL 无法在本地检查，总是返回 true，由 restic 在访问时报告仓库不存在
+pub fn is_repo_location(repo: &str) -> bool {
+    is_remote_repo(repo) || is_restic_repo(Path::new(repo))
+}
+
 /// 严格检查给定的路径是否为一个有效的 restic 仓库
 pub fn is_restic_repo(path: &Path) -> bool {
     if !path.is_dir() {
@@ -140,36 +186,26 @@ pub fn is_restic_repo(path: &Path) -> bool {
 /// # 参数
 /// - `restic_exe_path`: restic 可执行文件路径
 /// - `args`: 传递给 restic 的参数列表
-/// - `password`: 仓库密码
+/// - `password`: 仓库密码来源 (明文与环境变量经标准输入传递，文件与命令交给 restic 自行读取)
+///
+/// 当前任务通过 `with_restic_env` 设置的环境变量会一并传给 restic 进程。
 ///
 /// # 返回
 /// - `Ok(String)`: 命令成功执行的标准输出
-/// - `Err(String)`: 错误信息（包含标准错误输出）
-pub fn run_restic_command(restic_exe_path: &str, args: &[&str], password: &str) -> Result<String, String> {
-    let mut child = Command::new(restic_exe_path)
-        .args(args)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("启动 restic 进程失败: {}", e))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(password.as_bytes())
-             .and_then(|_| stdin.write_all(b"\n"))
-             .map_err(|e| format!("向 restic 写入密码失败: {}", e))?;
-    }
+/// - `Err(Error)`: 按 restic 输出归类的错误，无法归类时包含完整的标准输出与标准错误
+pub fn run_restic_command(restic_exe_path: &str, args: &[&str], password: &PasswordSource) -> Result<String, Error> {
+    run_restic_command_in(restic_exe_path, args, password, &JobOutput::default())
+}
 
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(ProgressStyle::default_spinner()
-        .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", " "])
-        .template("{spinner:.cyan} {msg}").unwrap());
-    spinner.set_message("正在执行 restic 命令，请稍候...");
-    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+/// 同 `run_restic_command`，但等待提示显示在指定的任务输出中 (用于并行批量任务)
+pub fn run_restic_command_in(restic_exe_path: &str, args: &[&str], password: &PasswordSource, output: &JobOutput) -> Result<String, Error> {
+    with_spinner(output, || run_restic(restic_exe_path, args, password))
+}
 
+/// 执行 restic 命令并返回标准输出，不显示任何提示 (供 `ResticClient` 等库代码使用)
+pub fn run_restic(restic_exe_path: &str, args: &[&str], password: &PasswordSource) -> Result<String, Error> {
+    let child = spawn_restic(restic_exe_path, args, password)?;
     let output = child.wait_with_output().map_err(|e| format!("等待 restic 进程失败: {}", e))?;
-    
-    spinner.finish_and_clear();
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -177,16 +213,54 @@ pub fn run_restic_command(restic_exe_path: &str, args: &[&str], password: &str)
     if output.status.success() {
         Ok(stdout)
     } else {
-        // 提供更友好的错误提示
-        if stderr.contains("wrong password or no key found") {
-            Err("密码错误。".to_string())
-        } else if stderr.contains("Is there a repository at the given location?") || stderr.contains("repository does not exist") {
-            Err("仓库路径无效或不存在。".to_string())
-        } else {
-            Err(format!(
-                "Restic 命令执行失败:\n--- 标准输出 ---\n{}\n--- 标准错误 ---\n{}",
-                stdout, stderr
-            ))
+        Err(Error::from_restic_output(&stdout, &stderr))
+    }
+}
+
+/// 在 `f` 执行期间，当前线程启动的所有 restic 进程都额外带上 `vars` 中的环境变量
+///
+/// 用于任务级的 `env` 配置 (例如 RESTIC_CACHE_DIR、GOMAXPROCS)；并行执行的任务各自在独立的线程中运行，互不影响。
+/// 嵌套调用时在外层的变量之上叠加，同名变量以内层为准。
+pub fn with_restic_env<T>(vars: &[(String, String)], f: impl FnOnce() -> T) -> T {
+    let previous = RESTIC_ENV.with(|env| {
+        let mut stacked = env.borrow().clone();
+        stacked.extend_from_slice(vars);
+        env.replace(stacked)
+    });
+    let result = f();
+    RESTIC_ENV.with(|env| *env.borrow_mut() = previous);
+    result
+}
+
+/// 启动 restic 子进程并按密码来源传入密码，标准输出与标准错误均为管道
+pub fn spawn_restic(restic_exe_path: &str, args: &[&str], password: &PasswordSource) -> Result<Child, Error> {
+    let mut command = Command::new(restic_exe_path);
+    command.args(args);
+    RESTIC_ENV.with(|vars| command.envs(vars.borrow().iter().map(|(key, value)| (key, value))));
+    let stdin_password = match password {
+        PasswordSource::Plain(p) => Some(p.clone()),
+        PasswordSource::Env(var) => Some(env::var(var).map_err(|_| format!("环境变量 '{}' 未设置。", var))?),
+        PasswordSource::File(file) => {
+            command.arg("--password-file").arg(file);
+            None
         }
+        PasswordSource::Command(cmd) => {
+            command.arg("--password-command").arg(cmd);
+            None
+        }
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("启动 restic 进程失败: {}", e))?;
+
+    if let (Some(mut stdin), Some(password)) = (child.stdin.take(), stdin_password) {
+        stdin.write_all(password.as_bytes())
+             .and_then(|_| stdin.write_all(b"\n"))
+             .map_err(|e| format!("向 restic 写入密码失败: {}", e))?;
     }
-}
\ No newline at end of file
+    Ok(child)
+}
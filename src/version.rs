@@ -0,0 +1,111 @@
+use crate::error::Error;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::process::Command;
+use std::sync::Mutex;
+
+/// 支持的最低 restic 版本，更旧的版本不支持仓库格式 v2 (压缩)、`--pack-size` 以及 `init --from-repo`；
+/// 本程序使用的其他参数 (包括 `backup --json` 进度) 在该版本中均已可用
+pub const MIN_SUPPORTED: ResticVersion = ResticVersion::new(0, 14, 0);
+/// 推荐的 restic 版本，低于该版本时给出升级提示
+pub const RECOMMENDED: ResticVersion = ResticVersion::new(0, 17, 0);
+
+lazy_static! {
+    /// 各 restic 可执行文件的版本，只检测一次
+    static ref DETECTED: Mutex<HashMap<String, Option<ResticVersion>>> = Mutex::new(HashMap::new());
+}
+
+/// restic 的版本号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResticVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ResticVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// 从 `restic version` 的输出中解析版本号，例如 "restic 0.17.3 compiled with go1.22.5 on linux/amd64"
+    pub fn parse(output: &str) -> Option<Self> {
+        let re = Regex::new(r"restic (\d+)\.(\d+)\.(\d+)").unwrap();
+        let caps = re.captures(output)?;
+        Some(Self::new(caps[1].parse().ok()?, caps[2].parse().ok()?, caps[3].parse().ok()?))
+    }
+
+    /// 低于 `MIN_SUPPORTED` 时返回 `Error::ResticTooOld`
+    pub fn ensure_supported(self) -> Result<(), Error> {
+        if self < MIN_SUPPORTED {
+            return Err(Error::ResticTooOld(format!("当前版本 {}，最低要求 {}", self, MIN_SUPPORTED)));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ResticVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// 执行 `restic version` 检测版本，结果按可执行文件缓存；无法执行或无法识别时返回 `None`
+pub fn detect(restic_exe_path: &str) -> Option<ResticVersion> {
+    if let Some(version) = DETECTED.lock().unwrap().get(restic_exe_path) {
+        return *version;
+    }
+    // 执行 restic 时不持有锁，避免一个卡住的 restic 阻塞其他线程的检测
+    let version = Command::new(restic_exe_path)
+        .arg("version")
+        .output()
+        .ok()
+        .and_then(|output| ResticVersion::parse(&String::from_utf8_lossy(&output.stdout)));
+    DETECTED.lock().unwrap().insert(restic_exe_path.to_string(), version);
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn parses_version_output() {
+        assert_eq!(
+            ResticVersion::parse("restic 0.17.3 compiled with go1.22.5 on linux/amd64"),
+            Some(ResticVersion::new(0, 17, 3))
+        );
+        assert_eq!(ResticVersion::parse("restic 1.2.10 (v1.2.10-dev)\n"), Some(ResticVersion::new(1, 2, 10)));
+        assert_eq!(ResticVersion::parse("restic 0.17 compiled with go1.22.5"), None);
+        assert_eq!(ResticVersion::parse("command not found"), None);
+    }
+
+    #[test]
+    fn orders_versions_numerically() {
+        assert!(ResticVersion::new(0, 9, 6) < ResticVersion::new(0, 14, 0));
+        assert!(ResticVersion::new(0, 17, 10) > ResticVersion::new(0, 17, 3));
+        assert!(ResticVersion::new(1, 0, 0) > ResticVersion::new(0, 99, 99));
+        assert_eq!(ResticVersion::new(0, 16, 4).to_string(), "0.16.4");
+    }
+
+    #[test]
+    fn rejects_versions_below_minimum() {
+        assert!(MIN_SUPPORTED.ensure_supported().is_ok());
+        assert!(ResticVersion::new(0, 18, 0).ensure_supported().is_ok());
+        let err = ResticVersion::new(0, 13, 1).ensure_supported().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResticTooOld);
+        assert!(err.to_string().contains("0.13.1"), "{}", err);
+    }
+
+    #[test]
+    fn caches_undetectable_versions() {
+        let missing = "/nonexistent/restic-for-version-test";
+        assert_eq!(detect(missing), None);
+        // 结果被缓存，再次检测不会重新执行
+        assert!(DETECTED.lock().unwrap().contains_key(missing));
+        assert_eq!(detect(missing), None);
+    }
+}